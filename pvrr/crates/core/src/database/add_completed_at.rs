@@ -0,0 +1,17 @@
+use super::*;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub(super) struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let alter = Table::alter()
+            .table(IdenVal("download_item"))
+            .add_column(column("completed_at").big_integer().null())
+            .to_owned();
+        manager.alter_table(alter).await
+    }
+}
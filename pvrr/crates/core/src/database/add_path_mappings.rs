@@ -0,0 +1,21 @@
+use super::*;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub(super) struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(path_mappings()).await
+    }
+}
+
+/// 下载器路径映射列表，json 数组
+fn path_mappings() -> TableAlterStatement {
+    Table::alter()
+        .table(IdenVal("downloader_client"))
+        .add_column(column("path_mappings").json().not_null().default("[]"))
+        .to_owned()
+}
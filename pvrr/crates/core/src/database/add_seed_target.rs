@@ -0,0 +1,31 @@
+use super::*;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub(super) struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // sqlite 每次只能添加一列
+        let columns = [
+            column("indexer").unsigned().null().to_owned(),
+            column("minimum_ratio").double().null().to_owned(),
+            column("minimum_seed_time").unsigned().null().to_owned(),
+            column("seeded")
+                .boolean()
+                .not_null()
+                .default(false)
+                .to_owned(),
+        ];
+        for mut column in columns {
+            let alter = Table::alter()
+                .table(IdenVal("download_item"))
+                .add_column(&mut column)
+                .to_owned();
+            manager.alter_table(alter).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use super::*;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub(super) struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(system_config()).await?;
+        Ok(())
+    }
+}
+
+fn system_config() -> TableCreateStatement {
+    create_table("system_config")
+        .if_not_exists()
+        .col(column("key").string().not_null().primary_key())
+        .col(column("value").string().not_null())
+        .to_owned()
+}
@@ -0,0 +1,38 @@
+use super::*;
+use async_trait::async_trait;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub(super) struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(download_item()).await?;
+        manager.create_index(download_item_index()).await?;
+        Ok(())
+    }
+}
+
+fn download_item() -> TableCreateStatement {
+    create_table("download_item")
+        .if_not_exists()
+        .col(id().primary_key())
+        .col(column("downloader").unsigned().not_null())
+        .col(column("item_id").string().not_null())
+        .col(column("status").integer().not_null())
+        .col(column("path").string().not_null().default(""))
+        .to_owned()
+}
+
+/// 同一下载器中的下载项 id 唯一
+fn download_item_index() -> IndexCreateStatement {
+    Index::create()
+        .if_not_exists()
+        .name("idx_download_item_downloader_item_id")
+        .table(IdenVal("download_item"))
+        .col(IdenVal("downloader"))
+        .col(IdenVal("item_id"))
+        .unique()
+        .to_owned()
+}
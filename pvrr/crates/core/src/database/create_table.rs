@@ -30,7 +30,7 @@ fn download_client() -> TableCreateStatement {
 }
 
 fn index_client() -> TableCreateStatement {
-    create_table("indexer_client")
+    create_table("index_client")
         .if_not_exists()
         .col(id().primary_key())
         .col(column("cat").integer().not_null())
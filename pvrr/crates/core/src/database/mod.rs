@@ -1,23 +1,90 @@
+mod add_completed_at;
+mod add_path_mappings;
+mod add_seed_target;
+mod add_target_met;
+mod create_config;
+mod create_download_item;
 mod create_table;
 
 // 公开数据库操作方法用于支持其他模块调用数据库操作
-pub use sea_orm::EntityTrait;
+pub use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Set};
 
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use once_cell::sync::Lazy;
-use sea_orm::DatabaseConnection;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 use sea_orm_migration::prelude::*;
+use std::env;
+use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
 
 // =================== init database ===================
 
-static DATABASE: Lazy<DatabaseConnection> = Lazy::new(|| todo!());
+static DATABASE: Lazy<OnceCell<DatabaseConnection>> = Lazy::new(OnceCell::new);
 
+/// 数据目录，使用内存数据库时不设置
+static DATA_PATH: once_cell::sync::OnceCell<PathBuf> = once_cell::sync::OnceCell::new();
+
+/// 获取全局数据库连接，需要在 [load] 或 [load_memory] 之后调用
 pub fn database() -> &'static DatabaseConnection {
-    &*DATABASE
+    DATABASE.get().expect("Database is not loaded")
+}
+
+/// 数据文件目录，使用内存数据库时返回 None
+pub fn data_path() -> Option<&'static Path> {
+    DATA_PATH.get().map(PathBuf::as_path)
+}
+
+/// 使用 DATA_PATH 目录下的 sqlite 文件初始化数据库，文件不存在时会自动创建，
+/// 数据库只会初始化一次，重复调用会直接返回
+pub async fn load() -> Result<()> {
+    DATABASE
+        .get_or_try_init(|| async { connect(sqlite_url()?).await })
+        .await?;
+    Ok(())
+}
+
+/// 使用内存数据库初始化，仅用于测试
+pub async fn load_memory() -> Result<()> {
+    let init = || async {
+        let mut opt = ConnectOptions::new("sqlite::memory:".to_owned());
+        // 内存数据库每个连接都是独立的，因此只能保持唯一连接
+        opt.max_connections(1).min_connections(1);
+        connect(opt).await
+    };
+    DATABASE.get_or_try_init(init).await?;
+    Ok(())
 }
 
-pub(crate) fn load() {
-    todo!()
+fn sqlite_url() -> Result<String> {
+    let data_path = env::var("DATA_PATH").context("Missing environment variable DATA_PATH")?;
+    let data_path = PathBuf::from(data_path);
+    std::fs::create_dir_all(&data_path)
+        .with_context(|| format!("Can't create data dir {}", data_path.display()))?;
+    let db_path = data_path.join("data.sqlite");
+    let db_path = db_path
+        .to_str()
+        .context("Environment variable DATA_PATH contains invalid unicode")?;
+    let url = format!("sqlite:file:{db_path}?mode=rwc");
+    let _ = DATA_PATH.set(data_path);
+    Ok(url)
+}
+
+async fn connect(opt: impl Into<ConnectOptions>) -> Result<DatabaseConnection> {
+    let mut opt = opt.into();
+
+    let sqlx_log_enable = env::var("SQLX_LOG_ENABLE").ok();
+    let sqlx_log_enable = sqlx_log_enable.and_then(|it| it.parse().ok());
+    opt.sqlx_logging(sqlx_log_enable.unwrap_or(false));
+
+    let database = Database::connect(opt).await;
+    let database = database.context("Database connect error")?;
+
+    Migrator::up(&database, None)
+        .await
+        .context("Database migrate error")?;
+
+    Ok(database)
 }
 
 // =================== migrator ===================
@@ -27,7 +94,16 @@ struct Migrator;
 #[async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(create_table::Migration)]
+        // 此处排序为版本号顺序
+        vec![
+            Box::new(create_table::Migration),
+            Box::new(create_config::Migration),
+            Box::new(create_download_item::Migration),
+            Box::new(add_path_mappings::Migration),
+            Box::new(add_seed_target::Migration),
+            Box::new(add_completed_at::Migration),
+            Box::new(add_target_met::Migration),
+        ]
     }
 }
 
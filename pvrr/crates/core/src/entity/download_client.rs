@@ -1,6 +1,11 @@
+use super::active_value;
 use sea_orm::entity::prelude::*;
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "i32", db_type = "Integer")]
 pub enum Category {
     #[sea_orm(num_value = 0)]
@@ -9,9 +14,13 @@ pub enum Category {
     Qbittorrent,
     #[sea_orm(num_value = 2)]
     Transmission,
+    #[sea_orm(num_value = 3)]
+    Deluge,
+    #[sea_orm(num_value = 4)]
+    Rtorrent,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "downloader_client")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -35,9 +44,57 @@ pub struct Model {
     /// 下载器分类标签
     #[sea_orm(nullable)]
     pub category: Option<String>,
+    /// 下载器路径到本地路径的映射，用于下载器有多个保存路径或 docker 挂载路径不同时
+    #[sea_orm(column_type = "Json")]
+    #[serde(default)]
+    pub path_mappings: PathMappings,
+}
+
+/// 下载器路径前缀到本地路径前缀的映射
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathMapping {
+    /// 下载器中的路径前缀
+    pub remote: String,
+    /// 本地识别的路径前缀
+    pub local: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct PathMappings(pub Vec<PathMapping>);
+
+impl PathMappings {
+    /// 检查映射的下载器路径不为空且不重复，本地路径必须存在
+    fn check(&self) -> Result<(), DbErr> {
+        for (i, mapping) in self.0.iter().enumerate() {
+            let remote = mapping.remote.trim();
+            if remote.is_empty() {
+                return Err(DbErr::Custom("Path mapping remote path is empty".into()));
+            }
+            let duplicate = self.0[..i]
+                .iter()
+                .any(|it| Path::new(it.remote.trim()) == Path::new(remote));
+            if duplicate {
+                let msg = format!("Path mapping remote path {remote} is duplicated");
+                return Err(DbErr::Custom(msg));
+            }
+            if !Path::new(&mapping.local).is_dir() {
+                let msg = format!("Path mapping local path {} not exists", mapping.local);
+                return Err(DbErr::Custom(msg));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(self, _: &C, _: bool) -> Result<Self, DbErr> {
+        if let Some(mappings) = active_value(&self.path_mappings) {
+            mappings.check()?;
+        }
+        Ok(self)
+    }
+}
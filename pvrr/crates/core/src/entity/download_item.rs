@@ -0,0 +1,64 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 下载状态
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+pub enum ItemStatus {
+    /// 下载中
+    #[sea_orm(num_value = 0)]
+    Downloading,
+    /// 下载完成，做种中
+    #[sea_orm(num_value = 1)]
+    Downloaded,
+    /// 下载完成，做种完成
+    #[sea_orm(num_value = 2)]
+    Complete,
+    /// 下载错误
+    #[sea_orm(num_value = 3)]
+    Error,
+}
+
+impl ItemStatus {
+    /// 是否已下载完成
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Downloaded | Self::Complete)
+    }
+}
+
+/// 下载器中跟踪的下载项，记录上次轮询时的状态
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "download_item")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载器中的下载项 id
+    pub item_id: String,
+    /// 下载状态
+    pub status: ItemStatus,
+    /// 下载项本地路径
+    pub path: String,
+    /// 下载项来源的索引器 id
+    #[sea_orm(nullable)]
+    pub indexer: Option<u32>,
+    /// 索引器要求的最小分享率
+    #[sea_orm(nullable)]
+    pub minimum_ratio: Option<f64>,
+    /// 索引器要求的最小做种时间（秒）
+    #[sea_orm(nullable)]
+    pub minimum_seed_time: Option<u32>,
+    /// 是否已达到做种目标并执行了做种规则操作
+    pub seeded: bool,
+    /// 下载完成时间（unix 时间戳），用于计算下载器不提供的做种时间
+    #[sea_orm(nullable)]
+    pub completed_at: Option<i64>,
+    /// 是否已达到做种目标并发布了事件，避免每次检查重复发布
+    pub target_met: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
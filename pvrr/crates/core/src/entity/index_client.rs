@@ -1,8 +1,10 @@
-use crate::request::cookies_change;
+use super::active_value;
+use crate::request::{cookies_change, cookies_check};
 use sea_orm::entity::prelude::*;
 use sea_orm::prelude::async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "i32", db_type = "Integer")]
 pub enum Category {
     #[sea_orm(num_value = 0)]
@@ -11,7 +13,7 @@ pub enum Category {
     Custom,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "index_client")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -37,11 +39,30 @@ pub enum Relation {}
 
 #[async_trait]
 impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(self, db: &C, insert: bool) -> Result<Self, DbErr> {
+        // 仅更新部分字段时，使用已保存的类型、url 和 cookies 检查
+        let partial = active_value(&self.cat).is_none()
+            || active_value(&self.url).is_none()
+            || active_value(&self.password).is_none();
+        let saved = match active_value(&self.id) {
+            Some(id) if !insert && partial => Entity::find_by_id(*id).one(db).await?,
+            _ => None,
+        };
+        let cat = active_value(&self.cat).or(saved.as_ref().map(|it| &it.cat));
+        let url = active_value(&self.url).or(saved.as_ref().map(|it| &it.url));
+        let cookies = active_value(&self.password).or(saved.as_ref().map(|it| &it.password));
+        if let (Some(Category::Custom), Some(url), Some(Some(cookies))) = (cat, url, cookies) {
+            cookies_check(url, cookies).map_err(|e| DbErr::Custom(e.to_string()))?;
+        }
+        Ok(self)
+    }
+
     async fn after_save<C: ConnectionTrait>(model: Model, _: &C, _: bool) -> Result<Model, DbErr> {
         // todo 可能有其他索引器使用 cookies
         if matches!(model.cat, Category::Custom) {
             if let Some(cookies) = model.password.as_ref() {
-                cookies_change(&model.url, cookies);
+                let change = cookies_change(&model.url, cookies);
+                change.map_err(|e| DbErr::Custom(e.to_string()))?;
             }
         }
         Ok(model)
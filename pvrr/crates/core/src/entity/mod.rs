@@ -1,8 +1,9 @@
 pub mod download_client;
+pub mod download_item;
 pub mod index_client;
 pub mod system_config;
 
-use sea_orm::ActiveModelTrait;
+use sea_orm::{ActiveModelTrait, ActiveValue};
 use serde::{de::Error, Deserialize, Deserializer};
 
 // 自动实现以支持 ActiveModel 直接反序列化
@@ -20,4 +21,13 @@ macro_rules! deserialize_active_model {
 }
 
 deserialize_active_model!(download_client);
+deserialize_active_model!(download_item);
 deserialize_active_model!(index_client);
+
+/// 获取 ActiveModel 中已有的字段值，未设置时返回 None
+fn active_value<V: Into<sea_orm::Value>>(value: &ActiveValue<V>) -> Option<&V> {
+    match value {
+        ActiveValue::Set(it) | ActiveValue::Unchanged(it) => Some(it),
+        ActiveValue::NotSet => None,
+    }
+}
@@ -1,25 +1,40 @@
-use crate::database::database;
+use super::active_value;
+use crate::setting::{setting_change, setting_check};
 use sea_orm::entity::prelude::*;
-use std::str::FromStr;
+use sea_orm::prelude::async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "system_config")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub key: String,
     pub value: String,
 }
 
-impl Model {
-    async fn get<V: FromStr>(key: &str) -> Option<V> {
-        // TODO need print log
-        let pair = Entity::find_by_id(key).one(database()).await;
-        let pair = pair.ok().flatten();
-        pair.and_then(|it| it.value.parse().ok())
-    }
-}
-
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(self, _: &C, _: bool) -> Result<Self, DbErr> {
+        if let (Some(key), Some(value)) = (active_value(&self.key), active_value(&self.value)) {
+            setting_check(key, value).map_err(|e| DbErr::Custom(format!("{e:#}")))?;
+        }
+        Ok(self)
+    }
+
+    async fn after_save<C: ConnectionTrait>(model: Model, _: &C, _: bool) -> Result<Model, DbErr> {
+        let change = setting_change(&model.key, Some(&model.value));
+        change.map_err(|e| DbErr::Custom(format!("{e:#}")))?;
+        Ok(model)
+    }
+
+    async fn after_delete<C: ConnectionTrait>(self, _: &C) -> Result<Self, DbErr> {
+        if let Some(key) = active_value(&self.key) {
+            let change = setting_change(key, None);
+            change.map_err(|e| DbErr::Custom(format!("{e:#}")))?;
+        }
+        Ok(self)
+    }
+}
@@ -1,4 +1,11 @@
-use std::any::Any;
+use crate::entity::download_item::ItemStatus;
+use crate::seeding::SeedAction;
+use once_cell::sync::Lazy;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tokio::sync::broadcast::{self, Receiver, Sender};
 
 /// 内部标识，禁止其他模块 impl
 pub(super) mod private {
@@ -6,7 +13,123 @@ pub(super) mod private {
 }
 
 /// 事件，用于传递信息
-pub trait Event: Any + Clone + private::Sealed {}
+pub trait Event: Any + Clone + Send + Sync + private::Sealed {}
 
-/// 事件总线
-pub struct EventBus;
+/// 每种事件的缓冲数量，订阅者处理过慢时会丢失最早的事件
+const EVENT_CAPACITY: usize = 256;
+
+/// 事件总线，每种事件使用独立的广播通道
+struct EventBus {
+    channels: RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+static EVENT_BUS: Lazy<EventBus> = Lazy::new(|| EventBus {
+    channels: RwLock::default(),
+});
+
+impl EventBus {
+    /// 获取事件对应的广播通道，不存在时进行创建
+    fn sender<E: Event>(&self) -> Sender<E> {
+        let id = TypeId::of::<E>();
+        if let Some(sender) = self.channels.read().unwrap().get(&id) {
+            return sender.downcast_ref::<Sender<E>>().unwrap().clone();
+        }
+        let mut channels = self.channels.write().unwrap();
+        let sender = channels
+            .entry(id)
+            .or_insert_with(|| Box::new(broadcast::channel::<E>(EVENT_CAPACITY).0));
+        sender.downcast_ref::<Sender<E>>().unwrap().clone()
+    }
+}
+
+/// 发布事件，没有订阅者时事件会被直接丢弃
+pub fn publish<E: Event>(event: E) {
+    let _ = EVENT_BUS.sender::<E>().send(event);
+}
+
+/// 订阅事件，只能收到订阅之后发布的事件
+pub fn subscribe<E: Event>() -> Receiver<E> {
+    EVENT_BUS.sender::<E>().subscribe()
+}
+
+macro_rules! impl_event {
+    ($($e:ident),+) => {
+        $(
+            impl private::Sealed for $e {}
+            impl Event for $e {}
+        )+
+    };
+}
+
+impl_event!(
+    TorrentAdded,
+    DownloadStatusChanged,
+    DownloadCompleted,
+    SeedingTargetMet,
+    IndexerSearchFinished,
+    FileImported
+);
+
+/// 下载器添加 torrent
+#[derive(Clone, Debug)]
+pub struct TorrentAdded {
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载项 id
+    pub id: String,
+    pub info_hash: String,
+}
+
+/// 下载项状态变化
+#[derive(Clone, Debug)]
+pub struct DownloadStatusChanged {
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载项 id
+    pub id: String,
+    /// 之前的状态，首次发现下载项时为 None
+    pub old: Option<ItemStatus>,
+    pub new: ItemStatus,
+}
+
+/// 下载器下载完成
+#[derive(Clone, Debug)]
+pub struct DownloadCompleted {
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载项 id
+    pub id: String,
+    /// 下载项本地路径
+    pub path: PathBuf,
+}
+
+/// 下载项达到做种目标
+#[derive(Clone, Debug)]
+pub struct SeedingTargetMet {
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载项 id
+    pub id: String,
+    /// 执行的操作，dry run 时为将要执行的操作
+    pub action: SeedAction,
+    pub dry_run: bool,
+}
+
+/// 索引器搜索完成
+#[derive(Clone, Debug)]
+pub struct IndexerSearchFinished {
+    /// 索引器 id
+    pub indexer: u32,
+    pub key_word: String,
+    /// 搜索结果数量，搜索失败时为错误信息
+    pub result: Result<usize, String>,
+    /// 跳过的无效结果及原因
+    pub skipped: Vec<String>,
+}
+
+/// 文件导入媒体库
+#[derive(Clone, Debug)]
+pub struct FileImported {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
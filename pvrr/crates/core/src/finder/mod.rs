@@ -1,3 +1,4 @@
+use crate::event::{publish, FileImported};
 use anyhow::{ensure, Result};
 use std::path::Path;
 use walkdir::{DirEntry, WalkDir};
@@ -33,6 +34,35 @@ fn copy_delete_file<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()
     Ok(())
 }
 
+/// 文件导入方式
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// 硬链接（需要两个路径在同一挂载卷）
+    HardLink,
+    /// 拷贝
+    Copy,
+    /// 移动（需要两个路径在同一挂载卷）
+    Move,
+    /// 先拷贝再删除
+    CopyDelete,
+}
+
+/// 导入文件，成功后发布 [FileImported] 事件
+pub fn import_file<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, mode: ImportMode) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    match mode {
+        ImportMode::HardLink => hard_link(from, to),
+        ImportMode::Copy => copy_file(from, to),
+        ImportMode::Move => modify_file_path(from, to),
+        ImportMode::CopyDelete => copy_delete_file(from, to),
+    }?;
+    publish(FileImported {
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+    });
+    Ok(())
+}
+
 /// 列出文件夹下所有的文件，
 /// 如果路径本身是文件，则返回文件本身
 fn tree<P: AsRef<Path>>(dir: P) -> Vec<FileEntry> {
@@ -2,7 +2,21 @@
 
 pub mod database;
 pub mod entity;
-mod event;
+pub mod event;
 pub mod finder;
+pub mod quality;
 pub mod request;
+pub mod seeding;
+pub mod setting;
 pub mod torrent;
+
+/// 加载数据库，并应用数据库中保存的设置，退出前需要调用 [shutdown]
+pub async fn load() -> anyhow::Result<()> {
+    database::load().await?;
+    request::load().await
+}
+
+/// 退出前调用，保存运行中尚未写入文件的数据
+pub fn shutdown() -> anyhow::Result<()> {
+    request::shutdown()
+}
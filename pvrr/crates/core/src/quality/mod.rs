@@ -0,0 +1,80 @@
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 资源来源
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSource {
+    #[default]
+    Unknown,
+    WebDL,
+    WebRip,
+    BluRay,
+    Remux,
+    HDTV,
+    SDTV,
+    DVD,
+    /// 枪版，包括 CAM、TS、TC
+    Cam,
+}
+
+/// 资源分辨率
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ItemResolution {
+    #[default]
+    #[serde(rename = "unknown")]
+    Unknown,
+    #[serde(rename = "480p")]
+    P480,
+    #[serde(rename = "576p")]
+    P576,
+    #[serde(rename = "720p")]
+    P720,
+    #[serde(rename = "1080p")]
+    P1080,
+    #[serde(rename = "2160p")]
+    P2160,
+}
+
+/// 质量配置，用于选择搜索结果，大小单位为 MB
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityProfile {
+    pub name: String,
+    /// 允许的分辨率，按优先级从高到低排列，为空时不限制
+    pub resolutions: Vec<ItemResolution>,
+    /// 允许的来源，按优先级从高到低排列，为空时不限制
+    pub sources: Vec<ItemSource>,
+    /// 优先选择的发布组，不区分大小写
+    pub preferred_groups: Vec<String>,
+    /// 拒绝的发布组，不区分大小写
+    pub rejected_groups: Vec<String>,
+    /// 标题中包含时加分的关键字，不区分大小写
+    pub preferred_keywords: Vec<String>,
+    /// 标题中包含时拒绝的关键字，不区分大小写
+    pub rejected_keywords: Vec<String>,
+    /// 每分钟时长的最小大小
+    pub min_size_per_minute: Option<f64>,
+    /// 每分钟时长的最大大小
+    pub max_size_per_minute: Option<f64>,
+    /// 免费下载时的加分，按免费比例计算
+    pub freeleech_bonus: i64,
+}
+
+/// 检查质量配置，名称不能为空或重复，最小大小不能超过最大大小
+pub(crate) fn profiles_check(profiles: &[QualityProfile]) -> Result<()> {
+    let mut names = HashSet::new();
+    for profile in profiles {
+        let name = profile.name.trim();
+        ensure!(!name.is_empty(), "Quality profile name can't be empty");
+        ensure!(names.insert(name), "Duplicate quality profile `{name}`");
+        if let (Some(min), Some(max)) = (profile.min_size_per_minute, profile.max_size_per_minute) {
+            ensure!(
+                min <= max,
+                "Quality profile `{name}` minimum size exceeds maximum size"
+            );
+        }
+    }
+    Ok(())
+}
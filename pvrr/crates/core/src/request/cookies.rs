@@ -1,10 +1,19 @@
+use super::CookieError;
+use crate::database::database;
+use crate::entity::index_client::{Category, Column, Entity};
+use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
 use cookie::Cookie;
-use cookie_store::CookieStore;
+use cookie_store::{CookieExpiration, CookieStore};
 use once_cell::sync::Lazy;
 use reqwest::header::HeaderValue;
 use reqwest::Url;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::Serialize;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 
 /// 全局 cookies
@@ -13,41 +22,210 @@ pub(super) static COOKIES: Lazy<Arc<Jar>> = Lazy::new(|| Arc::new(Jar::default()
 type CustomCookie = (Box<str>, Box<str>);
 type CustomCookieList = Arc<[CustomCookie]>;
 
-/// 转换单条 cookie
-fn covert_to_cookie(cookie: &str) -> Option<CustomCookie> {
-    cookie
-        .split_once("=")
-        .map(|(key, value)| (key.into(), value.into()))
+/// 转换单条 cookie，cookie 名称不能为空且不能含有分隔符和控制字符
+fn covert_to_cookie(cookie: &str) -> Result<CustomCookie, CookieError> {
+    let invalid = || CookieError::InvalidCookie(cookie.to_owned());
+    let (key, value) = cookie.split_once('=').ok_or_else(invalid)?;
+    let (key, value) = (key.trim(), value.trim());
+    let key_invalid =
+        |c: char| c.is_control() || c.is_whitespace() || "()<>@,;:\\\"/[]?={}".contains(c);
+    if key.is_empty() || key.chars().any(key_invalid) {
+        return Err(invalid());
+    }
+    if value.chars().any(|c| c.is_control() || c == ';') {
+        return Err(invalid());
+    }
+    Ok((key.into(), value.into()))
 }
 
-/// 转换全部 cookies
-fn covert_cookies(cookies: &str) -> CustomCookieList {
+/// 转换全部 cookies，忽略空白项
+fn covert_cookies(cookies: &str) -> Result<CustomCookieList, CookieError> {
     cookies
-        .split(";")
+        .split(';')
         .map(|it| it.trim())
-        .filter_map(covert_to_cookie)
+        .filter(|it| !it.is_empty())
+        .map(covert_to_cookie)
         .collect()
 }
 
+/// 自定义 cookies 对应的站点，支持域名和 ip，cookies 不区分端口
+fn cookies_host(url: &str) -> Result<Box<str>, CookieError> {
+    let url = Url::parse(url).map_err(|e| CookieError::InvalidUrl(format!("{url} ({e})")))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| CookieError::NoHost(url.to_string()))?;
+    Ok(host.into())
+}
+
+/// 检查自定义 cookies 设置
+pub(crate) fn cookies_check(url: &str, cookies: &str) -> Result<(), CookieError> {
+    cookies_host(url)?;
+    covert_cookies(cookies)?;
+    Ok(())
+}
+
 /// cookies 设置变化
-pub(crate) fn cookies_change(url: &str, cookies: &str) {
-    let url = match Url::parse(url) {
-        Ok(it) => it,
-        Err(_) => todo!("print error log"),
-    };
-    let domain = match url.domain() {
-        None => todo!("print error log"),
-        Some(it) => it,
-    };
-    let cookies = covert_cookies(cookies);
+pub(crate) fn cookies_change(url: &str, cookies: &str) -> Result<(), CookieError> {
+    let host = cookies_host(url)?;
+    let cookies = covert_cookies(cookies)?;
     let mut store = COOKIES.0.write().unwrap();
-    store.custom.insert(domain.into(), cookies);
+    store.custom.insert(host, cookies);
+    Ok(())
+}
+
+/// 加载索引器中自定义的 cookies
+pub(super) async fn load_custom_cookies() -> Result<()> {
+    let clients = Entity::find().filter(Column::Cat.eq(Category::Custom));
+    for client in clients.all(database()).await? {
+        if let Some(cookies) = client.password.as_ref() {
+            let change = cookies_change(&client.url, cookies);
+            change.with_context(|| format!("Index client {} cookies error", client.name))?;
+        }
+    }
+    Ok(())
+}
+
+/// cookie 信息，用于排查问题
+#[derive(Clone, Debug, Serialize)]
+pub struct CookieInfo {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// 过期时间（unix 时间戳），会话 cookie 为 None
+    pub expires: Option<i64>,
+    /// 是否为索引器设置中自定义的 cookie
+    pub custom: bool,
+}
+
+/// cookie 所属域名是否匹配所给域名
+fn domain_match(cookie_domain: &str, domain: &str) -> bool {
+    let cookie_domain = cookie_domain.trim_start_matches('.');
+    domain == cookie_domain || domain.ends_with(&format!(".{cookie_domain}"))
+}
+
+/// 列出所给域名使用的 cookies
+pub fn list_cookies(domain: &str) -> Vec<CookieInfo> {
+    let store = COOKIES.0.read().unwrap();
+    let mut list = vec![];
+
+    let custom = store.custom.iter().filter(|it| domain_match(it.0, domain));
+    for (custom_domain, cookies) in custom {
+        list.extend(cookies.iter().map(|(name, value)| CookieInfo {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: custom_domain.to_string(),
+            path: "/".to_owned(),
+            expires: None,
+            custom: true,
+        }));
+    }
+
+    let cookies = store.store.iter_unexpired();
+    let cookies = cookies.filter(|it| domain_match(&String::from(&it.domain), domain));
+    list.extend(cookies.map(|it| CookieInfo {
+        name: it.name().to_owned(),
+        value: it.value().to_owned(),
+        domain: String::from(&it.domain),
+        path: it.path.to_string(),
+        expires: match it.expires {
+            CookieExpiration::AtUtc(time) => Some(time.unix_timestamp()),
+            CookieExpiration::SessionEnd => None,
+        },
+        custom: false,
+    }));
+
+    list
+}
+
+/// 清除所给域名通过请求获得的 cookies，索引器设置中自定义的 cookies 不会被清除
+pub fn clear_cookies(domain: &str) {
+    let mut store = COOKIES.0.write().unwrap();
+    let cookies = store.store.iter_any();
+    let cookies = cookies.filter(|it| domain_match(&String::from(&it.domain), domain));
+    let keys: Vec<_> = cookies.map(MixCookieStore::cookie_key).collect();
+    for (domain, path, name) in keys {
+        store.store.remove(&domain, &path, &name);
+    }
+    store.dirty = true;
+}
+
+/// 将 cookies 保存到文件，会话 cookies 也会保存，以便重启后保持站点登录状态
+pub fn save_cookies(path: &Path) -> Result<()> {
+    let bytes = {
+        let mut store = COOKIES.0.write().unwrap();
+        store.prune();
+        store.dirty = false;
+        store.to_json()?
+    };
+    let result = write_file(path, &bytes);
+    if result.is_err() {
+        // 保存失败时等待下次重试
+        COOKIES.0.write().unwrap().dirty = true;
+    }
+    result
+}
+
+/// 从文件加载 cookies，已过期的 cookies 会被跳过
+pub fn load_cookies(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let reader = BufReader::new(File::open(path)?);
+    let cookies = CookieStore::load_json(reader).map_err(|e| anyhow!(e))?;
+    COOKIES.0.write().unwrap().store = cookies;
+    Ok(())
+}
+
+/// cookies 有变化时保存到文件
+pub fn flush_cookies(path: &Path) -> Result<()> {
+    let dirty = COOKIES.0.read().unwrap().dirty;
+    if dirty {
+        save_cookies(path)?;
+    }
+    Ok(())
+}
+
+/// 先写入临时文件再替换，防止写入中断导致文件损坏
+fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    File::create(&tmp)?.write_all(bytes)?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
 }
 
 #[derive(Default)]
 struct MixCookieStore {
     store: CookieStore,
     custom: HashMap<Box<str>, CustomCookieList>,
+    /// 上次保存后 store 是否有变化
+    dirty: bool,
+}
+
+impl MixCookieStore {
+    fn cookie_key(cookie: &cookie_store::Cookie) -> (String, String, String) {
+        let domain = String::from(&cookie.domain);
+        (domain, cookie.path.to_string(), cookie.name().to_owned())
+    }
+
+    /// 清理已过期的 cookies
+    fn prune(&mut self) {
+        let expired = self.store.iter_any().filter(|it| it.is_expired());
+        let keys: Vec<_> = expired.map(Self::cookie_key).collect();
+        for (domain, path, name) in keys {
+            self.store.remove(&domain, &path, &name);
+        }
+    }
+
+    /// 每行一个 cookie 的 json 格式，与 [CookieStore::load_json] 对应
+    fn to_json(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        for cookie in self.store.iter_unexpired() {
+            serde_json::to_writer(&mut bytes, cookie)?;
+            bytes.push(b'\n');
+        }
+        Ok(bytes)
+    }
 }
 
 #[derive(Default)]
@@ -57,7 +235,17 @@ impl Jar {
     fn custom_cookies(&self, domain: &str) -> Option<CustomCookieList> {
         let lock = self.0.read().unwrap();
         let cookies = lock.custom.get(domain);
-        cookies.map(|it| it.clone())
+        cookies.cloned()
+    }
+
+    /// 保存 Set-Cookie 和 FlareSolverr 解析结果中的 cookies
+    pub(super) fn store_cookies<I>(&self, cookies: I, url: &Url)
+    where
+        I: Iterator<Item = Cookie<'static>>,
+    {
+        let mut store = self.0.write().unwrap();
+        store.store.store_response_cookies(cookies, url);
+        store.dirty = true;
     }
 }
 
@@ -72,14 +260,14 @@ impl reqwest::cookie::CookieStore for Jar {
     #[rustfmt::skip]
     fn set_cookies(&self, cookies: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
         let iter = cookies.filter_map(build_resp_cookie);
-        self.0.write().unwrap().store.store_response_cookies(iter, url);
+        self.store_cookies(iter, url);
     }
 
     fn cookies(&self, url: &Url) -> Option<HeaderValue> {
         let mut map = HashMap::new();
 
         // 设置中自定义的 cookies，优先级低于原生 cookies
-        let custom = url.domain().and_then(|it| self.custom_cookies(it));
+        let custom = url.host_str().and_then(|it| self.custom_cookies(it));
         if let Some(cookies) = custom.as_ref() {
             for (key, value) in cookies.as_ref() {
                 map.insert(key.as_ref(), value.as_ref());
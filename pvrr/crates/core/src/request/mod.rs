@@ -1,17 +1,37 @@
 mod cookies;
+mod policy;
+mod proxy;
+#[allow(clippy::module_inception)]
 mod request;
 mod response;
+mod solver;
 
-pub(crate) use cookies::cookies_change;
-use cookies::COOKIES;
+use crate::database::data_path;
+use anyhow::{Context, Result};
+pub(crate) use cookies::{cookies_change, cookies_check};
+use cookies::{load_custom_cookies, COOKIES};
 use once_cell::sync::Lazy;
-use reqwest::Proxy;
-use std::sync::Mutex;
+pub(crate) use policy::{policy_change, policy_check};
+pub(crate) use proxy::{proxy_change, proxy_check};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::task::JoinHandle;
 
+pub use cookies::CookieInfo;
+pub use cookies::{clear_cookies, flush_cookies, list_cookies, load_cookies, save_cookies};
+pub use policy::RequestPolicy;
+pub use proxy::{proxy_test, ProxyConfig};
 pub use request::Req;
 pub use reqwest::{Method, StatusCode};
 pub use response::Resp;
+pub use solver::destroy_solver_sessions;
+
+/// cookies 定时保存间隔
+const COOKIES_FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// 数据目录中保存 cookies 的文件
+const COOKIES_FILE: &str = "cookies.json";
 
 /// 默认 client
 static DEFAULT_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
@@ -22,32 +42,51 @@ static DEFAULT_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
         .unwrap()
 });
 
-/// 代理 client
-static PROXY_CLIENT: Lazy<Mutex<reqwest::Client>> = Lazy::new(|| {
-    Mutex::new(
-        reqwest::Client::builder()
-            .cookie_provider(COOKIES.clone())
-            .build()
-            .unwrap(),
-    )
-});
+/// 代理 client，未设置代理时为 None
+static PROXY_CLIENT: Lazy<RwLock<Option<reqwest::Client>>> = Lazy::new(RwLock::default);
+
+/// 请求策略
+static POLICY: Lazy<RwLock<RequestPolicy>> = Lazy::new(RwLock::default);
+
+/// 定时保存 cookies 的任务，重复加载时替换之前的任务
+static FLUSH_TASK: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(Mutex::default);
 
-/// 代理设置变化
-pub(crate) fn proxy_change(url: &str, username: Option<&str>, password: Option<&str>) {
-    let mut proxy = match Proxy::all(url) {
-        Ok(it) => it,
-        Err(_) => return,
-    };
-    if let (Some(username), Some(password)) = (username, password) {
-        proxy = proxy.basic_auth(username, password);
+/// 加载代理、请求策略、cookies 等设置，需要在数据库加载后调用，
+/// 使用文件数据库时会从数据目录加载 cookies 并定时保存，退出前需要调用 [shutdown]
+pub async fn load() -> Result<()> {
+    proxy::load_proxy().await?;
+    policy::load_policy().await?;
+    load_custom_cookies().await?;
+    if let Some(path) = data_path() {
+        let path = path.join(COOKIES_FILE);
+        load_cookies(&path)?;
+        let task = tokio::spawn(flush_cookies_task(path));
+        if let Some(old) = FLUSH_TASK.lock().unwrap().replace(task) {
+            old.abort();
+        }
+    }
+    Ok(())
+}
+
+/// 退出前调用，停止定时保存并保存定时保存后有变化的 cookies，
+/// 不调用时最后一次定时保存后的 cookies 变化会丢失
+pub fn shutdown() -> Result<()> {
+    if let Some(task) = FLUSH_TASK.lock().unwrap().take() {
+        task.abort();
+    }
+    match data_path() {
+        Some(path) => flush_cookies(&path.join(COOKIES_FILE)),
+        None => Ok(()),
+    }
+}
+
+async fn flush_cookies_task(path: PathBuf) {
+    let mut interval = tokio::time::interval(COOKIES_FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        // 保存失败时会在下次定时保存时重试
+        let _ = flush_cookies(&path);
     }
-    let client = reqwest::Client::builder()
-        .cookie_provider(COOKIES.clone())
-        .proxy(proxy)
-        .build()
-        .unwrap();
-    let mut mutex = PROXY_CLIENT.lock().unwrap();
-    *mutex = client;
 }
 
 /// 默认 client
@@ -55,16 +94,18 @@ pub fn direct() -> Client {
     Client {
         client: DEFAULT_CLIENT.clone(),
         flare_solver: false,
+        rate_limit: true,
     }
 }
 
-/// 使用 proxy 的 client，
-/// 如果没有设置则默认使用系统代理
-pub fn proxy() -> Client {
-    Client {
-        client: PROXY_CLIENT.lock().unwrap().clone(),
+/// 使用 proxy 的 client，未设置代理时返回错误
+pub fn proxy() -> Result<Client> {
+    let client = PROXY_CLIENT.read().unwrap().clone();
+    Ok(Client {
+        client: client.context("Proxy is not set.")?,
         flare_solver: false,
-    }
+        rate_limit: true,
+    })
 }
 
 /// flare solver 代理的 client
@@ -72,15 +113,23 @@ pub fn flare_solver() -> Client {
     Client {
         client: DEFAULT_CLIENT.clone(),
         flare_solver: true,
+        rate_limit: true,
     }
 }
 
 pub struct Client {
     client: reqwest::Client,
     flare_solver: bool,
+    rate_limit: bool,
 }
 
 impl Client {
+    /// 不进行站点限速，用于访问下载器等本地服务
+    pub fn without_rate_limit(mut self) -> Self {
+        self.rate_limit = false;
+        self
+    }
+
     pub fn get(&self, url: impl AsRef<str>) -> Req {
         self.request(Method::GET, url)
     }
@@ -91,13 +140,61 @@ impl Client {
 
     pub fn request(&self, method: Method, url: impl AsRef<str>) -> Req {
         if self.flare_solver {
-            Req::flare_solver(self.client.clone(), method, url)
+            Req::flare_solver(self.client.clone(), method, url, self.rate_limit)
+        } else {
+            Req::default(self.client.clone(), method, url, self.rate_limit)
+        }
+    }
+}
+
+/// FlareSolverr 返回的错误
+#[derive(Error, Debug, Clone)]
+pub enum SolverError {
+    #[error("FlareSolverr timeout: {0}")]
+    Timeout(String),
+    #[error("FlareSolverr challenge not solved: {0}")]
+    ChallengeNotSolved(String),
+    /// FlareSolverr 重启或清理后会话不再存在
+    #[error("FlareSolverr session not found: {0}")]
+    SessionNotFound(String),
+    #[error("FlareSolverr error: {0}")]
+    Other(String),
+}
+
+impl SolverError {
+    /// FlareSolverr 只返回错误信息，根据信息内容区分错误类型
+    fn new(message: &str) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("timeout") {
+            Self::Timeout(message.to_owned())
+        } else if ["challenge", "captcha", "cloudflare"]
+            .iter()
+            .any(|it| lower.contains(it))
+        {
+            Self::ChallengeNotSolved(message.to_owned())
+        } else if lower.contains("session")
+            && ["not exist", "not found", "doesn't exist"]
+                .iter()
+                .any(|it| lower.contains(it))
+        {
+            Self::SessionNotFound(message.to_owned())
         } else {
-            Req::default(self.client.clone(), method, url)
+            Self::Other(message.to_owned())
         }
     }
 }
 
+/// 索引器自定义 cookies 设置错误
+#[derive(Error, Debug, Clone)]
+pub enum CookieError {
+    #[error("Invalid cookies url: {0}")]
+    InvalidUrl(String),
+    #[error("Cookies url has no host: {0}")]
+    NoHost(String),
+    #[error("Invalid cookie `{0}`, expect `name=value` separated by `;`")]
+    InvalidCookie(String),
+}
+
 // only support for build Request, make error cloneable
 #[derive(Error, Debug, Clone)]
 enum RequestError {
@@ -0,0 +1,131 @@
+use super::{Resp, POLICY};
+use crate::setting::REQUEST_POLICY;
+use anyhow::{ensure, Result};
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// 请求策略设置，时间单位均为毫秒
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestPolicy {
+    /// 同一站点允许的突发请求数（令牌桶容量）
+    pub burst: u32,
+    /// 同一站点令牌恢复间隔，0 表示不限速
+    pub interval: u64,
+    /// GET 和 HEAD 请求 5xx 和 429 响应的最大重试次数
+    pub max_retries: u32,
+    /// 首次重试等待时间，之后每次翻倍
+    pub retry_delay: u64,
+    /// 最长重试等待时间，Retry-After 超过此值时不再重试
+    pub max_retry_delay: u64,
+    /// 单次请求超时时间，0 表示不限制
+    pub timeout: u64,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            burst: 5,
+            interval: 2000,
+            max_retries: 3,
+            retry_delay: 1000,
+            max_retry_delay: 60000,
+            timeout: 30000,
+        }
+    }
+}
+
+impl RequestPolicy {
+    pub(super) fn timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+            .filter(|it| *it > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// 第 retries 次重试前的等待时间，不需要重试时返回 None
+    pub(super) fn retry_delay(&self, retries: u32, resp: &Resp) -> Option<Duration> {
+        let status = resp.status();
+        let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+        if !retry || retries >= self.max_retries {
+            return None;
+        }
+
+        let max_delay = Duration::from_millis(self.max_retry_delay);
+        let retry_after = resp.header("retry-after").and_then(parse_retry_after);
+        match retry_after {
+            Some(it) => Some(it).filter(|it| *it <= max_delay),
+            None => {
+                let delay = self.retry_delay.saturating_mul(1 << retries.min(16));
+                Some(Duration::from_millis(delay).min(max_delay))
+            }
+        }
+    }
+}
+
+/// Retry-After 可以是秒数或 http 日期
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// 检查请求策略设置是否可用
+pub(crate) fn policy_check(policy: &RequestPolicy) -> Result<()> {
+    ensure!(policy.burst > 0, "Request burst must be greater than 0");
+    Ok(())
+}
+
+/// 请求策略设置变化，已有的令牌桶按新设置重新计算
+pub(crate) fn policy_change(policy: &RequestPolicy) -> Result<()> {
+    policy_check(policy)?;
+    *POLICY.write().unwrap() = policy.clone();
+    BUCKETS.lock().unwrap().clear();
+    Ok(())
+}
+
+/// 从设置中加载请求策略
+pub(super) async fn load_policy() -> Result<()> {
+    policy_change(&REQUEST_POLICY.get().await?)
+}
+
+/// 站点令牌桶，令牌不足时预支令牌，预支的令牌数决定等待时间，
+/// 因此同一站点的请求会按顺序间隔发出
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+static BUCKETS: Lazy<Mutex<HashMap<Box<str>, Bucket>>> = Lazy::new(Mutex::default);
+
+/// 获取站点的请求令牌，令牌不足时等待
+pub(super) async fn acquire(host: &str, policy: &RequestPolicy) {
+    if policy.interval == 0 {
+        return;
+    }
+    let interval = Duration::from_millis(policy.interval).as_secs_f64();
+    let burst = f64::from(policy.burst);
+
+    let wait = {
+        let mut buckets = BUCKETS.lock().unwrap();
+        let now = Instant::now();
+        let bucket = buckets.entry(host.into()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() / interval;
+        bucket.tokens = (bucket.tokens + refill).min(burst) - 1.0;
+        bucket.updated = now;
+        Duration::from_secs_f64(-bucket.tokens.min(0.0) * interval)
+    };
+
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
@@ -0,0 +1,65 @@
+use super::{proxy, COOKIES, PROXY_CLIENT};
+use crate::setting::{PROXY, PROXY_CHECK_URL};
+use anyhow::{ensure, Result};
+use reqwest::Proxy;
+use serde::{Deserialize, Serialize};
+
+/// 代理设置，url 为空时不使用代理
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// 代理地址，支持 http、https、socks5、socks5h
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+impl ProxyConfig {
+    fn build(&self) -> Result<Option<reqwest::Client>> {
+        let url = self.url.trim();
+        if url.is_empty() {
+            return Ok(None);
+        }
+
+        let scheme = url.split_once("://").map(|it| it.0);
+        let support = matches!(scheme, Some("http" | "https" | "socks5" | "socks5h"));
+        ensure!(support, "Unsupported proxy url: {url}");
+
+        let mut proxy = Proxy::all(url)?;
+        if !self.username.is_empty() {
+            proxy = proxy.basic_auth(&self.username, &self.password);
+        }
+        let client = reqwest::Client::builder()
+            .cookie_provider(COOKIES.clone())
+            .proxy(proxy)
+            .build()?;
+        Ok(Some(client))
+    }
+}
+
+/// 检查代理设置是否可用
+pub(crate) fn proxy_check(config: &ProxyConfig) -> Result<()> {
+    config.build()?;
+    Ok(())
+}
+
+/// 代理设置变化
+pub(crate) fn proxy_change(config: &ProxyConfig) -> Result<()> {
+    let client = config.build()?;
+    *PROXY_CLIENT.write().unwrap() = client;
+    Ok(())
+}
+
+/// 从设置中加载代理
+pub(super) async fn load_proxy() -> Result<()> {
+    proxy_change(&PROXY.get().await?)
+}
+
+/// 代理连接测试，返回通过代理访问时的出口 ip
+pub async fn proxy_test() -> Result<String> {
+    let url = PROXY_CHECK_URL.get().await?;
+    let resp = proxy()?.get(url).send().await?;
+    let ip = resp.error_for_status()?.text().await?;
+    Ok(ip.trim().to_owned())
+}
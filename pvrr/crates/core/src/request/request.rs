@@ -1,10 +1,15 @@
-use super::{RequestError, Resp, COOKIES};
-use crate::entity::system_config::Model as SystemConfig;
+use super::policy::acquire;
+use super::response::Solution;
+use super::solver::Solver;
+use super::{RequestError, Resp, SolverError, COOKIES, POLICY};
 use anyhow::Result;
 use reqwest::cookie::CookieStore;
-use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Method, RequestBuilder, Url};
 use serde::Serialize;
 use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize, Clone)]
 struct FlareSolverCookie {
@@ -23,7 +28,7 @@ impl FlareSolverCookie {
 }
 
 #[derive(Serialize, Clone)]
-struct FlareSolverParam {
+pub(super) struct FlareSolverParam {
     cmd: &'static str,
     url: String,
     #[serde(skip)]
@@ -32,12 +37,35 @@ struct FlareSolverParam {
     cookies: Vec<FlareSolverCookie>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "postData")]
     post_data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<Arc<str>>,
+    #[serde(rename = "maxTimeout")]
+    max_timeout: u64,
+}
+
+impl FlareSolverParam {
+    /// 直接请求，用于下载 FlareSolverr 无法返回的二进制文件
+    fn direct(&self, client: &Client) -> RequestBuilder {
+        let mut req = match self.post_data.as_ref() {
+            Some(data) => client
+                .post(&self.url)
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(data.clone()),
+            None => client.get(&self.url),
+        };
+        if let Some(timeout) = POLICY.read().unwrap().timeout() {
+            req = req.timeout(timeout);
+        }
+        req
+    }
 }
 
 #[derive(Clone)]
 pub struct SolverReq {
     client: Client,
     param: Result<FlareSolverParam, RequestError>,
+    /// 页面解析超时时间，未设置时使用 FlareSolverr 设置
+    timeout: Option<Duration>,
 }
 
 impl SolverReq {
@@ -45,6 +73,7 @@ impl SolverReq {
         Self {
             client,
             param: Err(RequestError::FlareSolverUnsupportedMethod(method)),
+            timeout: None,
         }
     }
 
@@ -57,7 +86,10 @@ impl SolverReq {
                 query: None,
                 cookies: vec![],
                 post_data: None,
+                session: None,
+                max_timeout: 0,
             }),
+            timeout: None,
         }
     }
 
@@ -70,7 +102,10 @@ impl SolverReq {
                 query: None,
                 cookies: vec![],
                 post_data: Some(String::default()),
+                session: None,
+                max_timeout: 0,
             }),
+            timeout: None,
         }
     }
 
@@ -80,7 +115,7 @@ impl SolverReq {
                 Ok(data) => match param.query.as_mut() {
                     None => param.query = Some(data),
                     Some(it) => {
-                        it.push_str("&");
+                        it.push('&');
                         it.push_str(&data);
                     }
                 },
@@ -102,13 +137,19 @@ impl SolverReq {
         self
     }
 
+    fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     fn err(mut self, func: &str) -> Self {
         self.param = Err(RequestError::FlareSolverUnsupportedFunc(func.to_owned()));
         self
     }
 
-    async fn send(self) -> Result<Response> {
+    async fn send(self) -> Result<Solution> {
         let mut param = self.param?;
+        let solver = Solver::load(self.client.clone()).await?;
 
         // 尝试设置 cookies
         let url = Url::parse(&param.url)?;
@@ -123,85 +164,186 @@ impl SolverReq {
 
         // 设置 url query
         if let Some(query) = param.query.as_ref() {
-            param.url.push_str("?");
+            param.url.push('?');
             param.url.push_str(query);
         }
 
-        // todo update url from database
-        let req = self.client.post("").json(&param);
-        Ok(req.send().await?)
+        // 同一站点复用浏览器会话
+        param.session = solver.session(&url).await?;
+        param.max_timeout = match self.timeout {
+            Some(it) => it.as_millis() as u64,
+            None => solver.max_timeout(),
+        };
+
+        let solution = match solver.request(&param).await {
+            Err(e) if is_session_not_found(&e) => {
+                // 会话已失效时重新创建会话并重试一次
+                if let Some(session) = param.session.take() {
+                    solver.forget(&session);
+                }
+                param.session = solver.session(&url).await?;
+                solver.request(&param).await?
+            }
+            result => result?,
+        };
+        Ok(solution.with_download(param.direct(&self.client)))
     }
 }
 
-pub enum Req {
+fn is_session_not_found(e: &anyhow::Error) -> bool {
+    let e = e.downcast_ref::<SolverError>();
+    matches!(e, Some(SolverError::SessionNotFound(_)))
+}
+
+enum ReqKind {
     Default(RequestBuilder),
     FlareSolver(SolverReq),
 }
 
+/// 请求，发送时按站点限速，GET 和 HEAD 请求的 5xx 和 429 响应会按请求策略重试
+pub struct Req {
+    kind: ReqKind,
+    /// 请求站点，用于限速
+    host: Option<Box<str>>,
+    rate_limit: bool,
+    /// 是否可以重试，其他方法的请求（例如向下载器添加下载）不一定是幂等的，因此不重试
+    retry: bool,
+}
+
 impl Req {
-    pub(super) fn default(client: Client, method: Method, url: impl AsRef<str>) -> Self {
-        let builder = client.request(method, url.as_ref());
-        Self::Default(builder)
+    fn new(kind: ReqKind, method: &Method, url: &str, rate_limit: bool) -> Self {
+        let url = Url::parse(url).ok();
+        let host = url.as_ref().and_then(Url::host_str).map(Into::into);
+        Self {
+            kind,
+            host,
+            rate_limit,
+            retry: matches!(*method, Method::GET | Method::HEAD),
+        }
     }
 
-    pub(super) fn flare_solver(client: Client, method: Method, url: impl AsRef<str>) -> Self {
-        match method {
-            Method::GET => Self::FlareSolver(SolverReq::get(client, url)),
-            Method::POST => Self::FlareSolver(SolverReq::post(client, url)),
-            _ => Self::FlareSolver(SolverReq::unsupported(client, method)),
+    pub(super) fn default(
+        client: Client,
+        method: Method,
+        url: impl AsRef<str>,
+        rate_limit: bool,
+    ) -> Self {
+        let mut builder = client.request(method.clone(), url.as_ref());
+        if let Some(timeout) = POLICY.read().unwrap().timeout() {
+            builder = builder.timeout(timeout);
         }
+        Self::new(ReqKind::Default(builder), &method, url.as_ref(), rate_limit)
+    }
+
+    pub(super) fn flare_solver(
+        client: Client,
+        method: Method,
+        url: impl AsRef<str>,
+        rate_limit: bool,
+    ) -> Self {
+        let req = match method {
+            Method::GET => SolverReq::get(client, &url),
+            Method::POST => SolverReq::post(client, &url),
+            _ => SolverReq::unsupported(client, method.clone()),
+        };
+        Self::new(ReqKind::FlareSolver(req), &method, url.as_ref(), rate_limit)
+    }
+
+    fn map(
+        mut self,
+        default: impl FnOnce(RequestBuilder) -> RequestBuilder,
+        flare_solver: impl FnOnce(SolverReq) -> SolverReq,
+    ) -> Self {
+        self.kind = match self.kind {
+            ReqKind::Default(it) => ReqKind::Default(default(it)),
+            ReqKind::FlareSolver(it) => ReqKind::FlareSolver(flare_solver(it)),
+        };
+        self
     }
 
     pub fn header(self, key: &'static str, value: impl AsRef<str>) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.header(key, value.as_ref())),
-            Req::FlareSolver(it) => Req::FlareSolver(it.err("set header")),
-        }
+        self.map(
+            |it| it.header(key, value.as_ref()),
+            |it| it.err("set header"),
+        )
     }
 
     pub fn basic_auth<U: Display, P: Display>(self, username: U, password: Option<P>) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.basic_auth(username, password)),
-            Req::FlareSolver(it) => Req::FlareSolver(it.err("set basic auth")),
-        }
+        self.map(
+            |it| it.basic_auth(username, password),
+            |it| it.err("set basic auth"),
+        )
     }
 
     pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.query(query)),
-            Req::FlareSolver(it) => Req::FlareSolver(it.query(query)),
-        }
+        self.map(|it| it.query(query), |it| it.query(query))
     }
 
     pub fn form<T: Serialize + ?Sized>(self, form: &T) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.form(form)),
-            Req::FlareSolver(it) => Req::FlareSolver(it.post_data(form)),
-        }
+        self.map(|it| it.form(form), |it| it.post_data(form))
     }
 
     pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.json(&json)),
-            Req::FlareSolver(it) => Req::FlareSolver(it.err("json body")),
+        self.map(|it| it.json(&json), |it| it.err("json body"))
+    }
+
+    pub fn body(self, body: impl Into<String>) -> Self {
+        self.map(|it| it.body(body.into()), |it| it.err("set body"))
+    }
+
+    /// 设置本次请求的超时时间，覆盖请求策略中的设置，
+    /// 使用 FlareSolverr 时为页面解析超时时间
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|it| it.timeout(timeout), |it| it.timeout(timeout))
+    }
+
+    fn try_clone(&self) -> Option<Self> {
+        let kind = match &self.kind {
+            ReqKind::Default(it) => ReqKind::Default(it.try_clone()?),
+            ReqKind::FlareSolver(it) => ReqKind::FlareSolver(it.clone()),
+        };
+        Some(Self {
+            kind,
+            host: self.host.clone(),
+            rate_limit: self.rate_limit,
+            retry: self.retry,
+        })
+    }
+
+    async fn send_once(self) -> Result<Resp> {
+        let policy = POLICY.read().unwrap().clone();
+        if let Some(host) = self.host.as_deref().filter(|_| self.rate_limit) {
+            acquire(host, &policy).await;
+        }
+        match self.kind {
+            ReqKind::Default(it) => Ok(Resp::Default(it.send().await?)),
+            ReqKind::FlareSolver(it) => Ok(Resp::FlareSolver(it.send().await?)),
         }
     }
 
     pub async fn send(self) -> Result<Resp> {
-        let flare_solver = matches!(self, Req::FlareSolver(_));
-        let resp = match self {
-            Req::Default(it) => it.send().await?,
-            Req::FlareSolver(it) => it.send().await?,
-        };
-        Resp::new(resp, flare_solver).await
+        let mut req = self;
+        let mut retries = 0;
+        loop {
+            // 请求体为流时无法复制，此时不进行重试
+            let retry = req.try_clone().filter(|it| it.retry);
+            let resp = req.send_once().await?;
+            let policy = POLICY.read().unwrap().clone();
+            let delay = policy.retry_delay(retries, &resp);
+            match (retry, delay) {
+                (Some(it), Some(delay)) => {
+                    tokio::time::sleep(delay).await;
+                    req = it;
+                    retries += 1;
+                }
+                _ => return Ok(resp),
+            }
+        }
     }
 }
 
 impl Clone for Req {
     fn clone(&self) -> Self {
-        match self {
-            Req::Default(it) => Req::Default(it.try_clone().unwrap()),
-            Req::FlareSolver(it) => Req::FlareSolver(it.clone()),
-        }
+        self.try_clone().unwrap()
     }
 }
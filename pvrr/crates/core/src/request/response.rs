@@ -1,32 +1,138 @@
-use anyhow::{bail, Result};
+use super::SolverError;
+use anyhow::{bail, Context, Result};
 use bytes::Bytes;
-use reqwest::{Response, StatusCode};
+use cookie::time::{Duration, OffsetDateTime};
+use cookie::Cookie;
+use reqwest::header::USER_AGENT;
+use reqwest::{RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::collections::HashMap;
 
+/// FlareSolverr 返回的 cookie
 #[derive(Deserialize)]
-pub struct SolverResp {
+#[serde(rename_all = "camelCase")]
+struct SolverCookie {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+    /// unix 时间戳（秒），会话 cookie 为 -1
+    expires: Option<f64>,
+    #[serde(default)]
+    http_only: bool,
+    #[serde(default)]
+    secure: bool,
+}
+
+impl SolverCookie {
+    fn to_cookie(&self) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.name.clone(), self.value.clone());
+        if let Some(domain) = self.domain.as_ref() {
+            cookie.set_domain(domain.clone());
+        }
+        if let Some(path) = self.path.as_ref() {
+            cookie.set_path(path.clone());
+        }
+        if let Some(expires) = self.expires.filter(|it| *it > 0.0) {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            cookie.set_max_age(Duration::seconds(expires as i64 - now));
+        }
+        cookie.set_http_only(self.http_only);
+        cookie.set_secure(self.secure);
+        cookie
+    }
+}
+
+/// FlareSolverr 解析结果
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Solution {
     url: String,
     status: u16,
+    #[serde(default)]
     headers: HashMap<String, String>,
+    #[serde(default)]
     response: String,
+    #[serde(default)]
+    cookies: Vec<SolverCookie>,
+    #[serde(default)]
+    user_agent: String,
+    /// 直接下载使用的请求，与原请求使用相同的 client、请求方法和请求体
+    #[serde(skip)]
+    download: Option<Box<RequestBuilder>>,
 }
 
-pub enum Resp {
-    Default(Response),
-    FlareSolver(SolverResp),
+impl Solution {
+    pub(super) fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(super) fn cookies(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        self.cookies.iter().map(SolverCookie::to_cookie)
+    }
+
+    pub(super) fn with_download(mut self, req: RequestBuilder) -> Self {
+        self.download = Some(Box::new(req));
+        self
+    }
+
+    /// FlareSolverr 只能返回页面文本，二进制文件（例如 torrent）需要使用解析得到的
+    /// cookies 和 User-Agent 直接请求
+    async fn download(self) -> Result<Bytes> {
+        let req = self
+            .download
+            .context("FlareSolverr solution without request")?;
+        let mut req = *req;
+        if !self.user_agent.is_empty() {
+            req = req.header(USER_AGENT, self.user_agent);
+        }
+        let resp = req.send().await?.error_for_status()?;
+        Ok(resp.bytes().await?)
+    }
 }
 
-impl Resp {
-    pub(super) async fn new(resp: Response, flare_solver: bool) -> Result<Self> {
-        if flare_solver {
-            Ok(Self::FlareSolver(resp.json().await?))
-        } else {
-            Ok(Self::Default(resp))
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SolverStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// FlareSolverr 响应
+#[derive(Deserialize)]
+pub(super) struct SolverResp {
+    status: SolverStatus,
+    #[serde(default)]
+    message: String,
+    solution: Option<Solution>,
+}
+
+impl SolverResp {
+    /// 检查 FlareSolverr 执行结果
+    pub(super) fn check(&self) -> Result<(), SolverError> {
+        match self.status {
+            SolverStatus::Ok | SolverStatus::Warning => Ok(()),
+            SolverStatus::Error => Err(SolverError::new(&self.message)),
         }
     }
 
+    /// 获取页面解析结果
+    pub(super) fn into_solution(self) -> Result<Solution, SolverError> {
+        self.check()?;
+        let message = "FlareSolverr response without solution";
+        self.solution
+            .ok_or_else(|| SolverError::Other(message.to_owned()))
+    }
+}
+
+pub enum Resp {
+    Default(Response),
+    FlareSolver(Solution),
+}
+
+impl Resp {
     pub fn status(&self) -> StatusCode {
         match self {
             Resp::Default(it) => it.status(),
@@ -36,6 +142,15 @@ impl Resp {
         }
     }
 
+    /// FlareSolverr 解析时使用的 User-Agent，
+    /// 解析得到的 cookies 需要配合相同的 User-Agent 使用
+    pub fn solver_user_agent(&self) -> Option<&str> {
+        match self {
+            Resp::Default(_) => None,
+            Resp::FlareSolver(it) => Some(it.user_agent.as_str()).filter(|it| !it.is_empty()),
+        }
+    }
+
     pub fn header(&self, name: &str) -> Option<&str> {
         match self {
             Resp::Default(it) => it.headers().get(name).and_then(|it| it.to_str().ok()),
@@ -46,7 +161,7 @@ impl Resp {
     pub async fn bytes(self) -> Result<Bytes> {
         match self {
             Resp::Default(it) => Ok(it.bytes().await?),
-            Resp::FlareSolver(it) => Ok(Bytes::from(it.response)),
+            Resp::FlareSolver(it) => it.download().await,
         }
     }
 
@@ -70,7 +185,7 @@ impl Resp {
             Resp::FlareSolver(it) => {
                 let status = StatusCode::from_u16(it.status)?;
                 if status.is_client_error() || status.is_server_error() {
-                    bail!("HTTP status error ({status}) for url ({})", it.url)
+                    bail!("HTTP status error ({status}) for url ({})", it.url())
                 } else {
                     Ok(Self::FlareSolver(it))
                 }
@@ -0,0 +1,125 @@
+use super::request::FlareSolverParam;
+use super::response::{Solution, SolverResp};
+use super::{COOKIES, DEFAULT_CLIENT};
+use crate::setting::{FLARE_SOLVER_TIMEOUT, FLARE_SOLVER_URL};
+use anyhow::{ensure, Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// 已创建的 FlareSolverr 会话
+#[derive(Default)]
+struct SessionSet {
+    /// 创建会话时的 FlareSolverr 地址，地址变化后之前的会话全部失效
+    endpoint: String,
+    sessions: HashSet<Arc<str>>,
+}
+
+static SESSIONS: Lazy<Mutex<SessionSet>> = Lazy::new(Mutex::default);
+
+#[derive(Serialize)]
+struct SessionParam<'a> {
+    cmd: &'static str,
+    session: &'a str,
+}
+
+/// FlareSolverr 服务配置
+pub(super) struct Solver {
+    client: Client,
+    endpoint: String,
+    max_timeout: u64,
+}
+
+impl Solver {
+    /// 从设置中读取 FlareSolverr 地址和超时时间
+    pub(super) async fn load(client: Client) -> Result<Self> {
+        let url = FLARE_SOLVER_URL.get().await?;
+        let url = url.trim().trim_end_matches('/');
+        ensure!(!url.is_empty(), "FlareSolverr url is not set.");
+        Ok(Self {
+            client,
+            endpoint: format!("{url}/v1"),
+            max_timeout: FLARE_SOLVER_TIMEOUT.get().await?,
+        })
+    }
+
+    pub(super) fn max_timeout(&self) -> u64 {
+        self.max_timeout
+    }
+
+    /// 获取站点对应的会话，不存在时进行创建，
+    /// 同一站点的请求复用同一个浏览器会话，避免重复通过 Cloudflare 验证
+    pub(super) async fn session(&self, url: &Url) -> Result<Option<Arc<str>>> {
+        let host = match url.host_str() {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        let session: Arc<str> = format!("pvrr-{host}").into();
+
+        {
+            let mut set = SESSIONS.lock().unwrap();
+            if set.endpoint != self.endpoint {
+                set.endpoint = self.endpoint.clone();
+                set.sessions.clear();
+            }
+            if set.sessions.contains(&session) {
+                return Ok(Some(session));
+            }
+        }
+
+        self.session_cmd("sessions.create", &session).await?;
+        SESSIONS.lock().unwrap().sessions.insert(session.clone());
+        Ok(Some(session))
+    }
+
+    /// 移除已失效的会话，下次获取时重新创建
+    pub(super) fn forget(&self, session: &str) {
+        SESSIONS.lock().unwrap().sessions.remove(session);
+    }
+
+    /// 发送请求，并将解析得到的 cookies 保存到全局 cookies 中，
+    /// 之后的直接请求可以不经过 FlareSolverr
+    pub(super) async fn request(&self, param: &FlareSolverParam) -> Result<Solution> {
+        let req = self.client.post(&self.endpoint).json(param);
+        let solution = self.send(req).await?.into_solution()?;
+        if let Ok(url) = Url::parse(solution.url()) {
+            COOKIES.store_cookies(solution.cookies(), &url);
+        }
+        Ok(solution)
+    }
+
+    async fn session_cmd(&self, cmd: &'static str, session: &str) -> Result<()> {
+        let req = self.client.post(&self.endpoint);
+        let req = req.json(&SessionParam { cmd, session });
+        self.send(req).await?.check()?;
+        Ok(())
+    }
+
+    /// FlareSolverr 执行失败时 http 状态码为 500，因此不检查状态码
+    async fn send(&self, req: RequestBuilder) -> Result<SolverResp> {
+        let resp = req.send().await?;
+        let resp = resp.json().await;
+        resp.context("FlareSolverr response decode error")
+    }
+}
+
+/// 销毁所有已创建的 FlareSolverr 会话，释放 FlareSolverr 中的浏览器实例
+pub async fn destroy_solver_sessions() -> Result<()> {
+    let sessions = std::mem::take(&mut SESSIONS.lock().unwrap().sessions);
+    if sessions.is_empty() {
+        return Ok(());
+    }
+
+    let solver = Solver::load(DEFAULT_CLIENT.clone()).await?;
+    let mut result = Ok(());
+    for session in sessions {
+        let destroy = solver.session_cmd("sessions.destroy", &session).await;
+        // 单个会话销毁失败不影响其他会话
+        if destroy.is_err() && result.is_ok() {
+            result = destroy;
+        }
+    }
+    result
+}
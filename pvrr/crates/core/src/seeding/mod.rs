@@ -0,0 +1,76 @@
+use crate::entity::download_item::Model;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 做种目标达成后对下载项执行的操作
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedAction {
+    /// 暂停下载项
+    #[default]
+    Pause,
+    /// 从下载器中删除下载项，保留已下载的文件
+    Remove,
+    /// 从下载器中删除下载项和已下载的文件
+    RemoveWithData,
+}
+
+/// 做种规则设置，做种时间单位为秒
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeedingRule {
+    /// 是否在轮询下载器后检查做种目标
+    pub enabled: bool,
+    /// 只生成报告，不对下载项执行操作
+    pub dry_run: bool,
+    /// 达到做种目标后执行的操作
+    pub action: SeedAction,
+    /// 最小分享率，设置后覆盖索引器的要求
+    pub minimum_ratio: Option<f64>,
+    /// 最小做种时间，设置后覆盖索引器的要求
+    pub minimum_seed_time: Option<u32>,
+}
+
+impl SeedingRule {
+    /// 下载项的做种目标，全局设置优先于下载项来源索引器的要求，
+    /// 都没有设置时返回 None，此时不处理该下载项
+    pub fn target(&self, item: &Model) -> Option<SeedTarget> {
+        let target = SeedTarget {
+            minimum_ratio: self.minimum_ratio.or(item.minimum_ratio),
+            minimum_seed_time: self.minimum_seed_time.or(item.minimum_seed_time),
+        };
+        let empty = target.minimum_ratio.is_none() && target.minimum_seed_time.is_none();
+        Some(target).filter(|_| !empty)
+    }
+}
+
+/// 下载项来源的索引器及其做种要求，做种时间单位为秒
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeedSource {
+    /// 索引器 id
+    pub indexer: u32,
+    pub minimum_ratio: Option<f64>,
+    pub minimum_seed_time: Option<u32>,
+}
+
+/// 下载项的做种目标，做种时间单位为秒
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SeedTarget {
+    pub minimum_ratio: Option<f64>,
+    pub minimum_seed_time: Option<u32>,
+}
+
+impl SeedTarget {
+    /// 是否达到全部做种要求，无法获取做种时间时视为未达到做种时间要求
+    pub fn is_met(&self, ratio: f64, seeding_time: Option<Duration>) -> bool {
+        let ratio_met = self.minimum_ratio.is_none_or(|it| ratio >= it);
+        let seeding_time = seeding_time.map(|it| it.as_secs());
+        let min_seed_time = self.minimum_seed_time.map(u64::from);
+        let time_met = match (min_seed_time, seeding_time) {
+            (None, _) => true,
+            (Some(min), Some(time)) => time >= min,
+            (Some(_), None) => false,
+        };
+        ratio_met && time_met
+    }
+}
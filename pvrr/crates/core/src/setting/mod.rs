@@ -0,0 +1,144 @@
+use crate::database::database;
+use crate::entity::system_config::{ActiveModel, Entity};
+use crate::quality::{profiles_check, QualityProfile};
+use crate::request::{policy_change, policy_check, proxy_change, proxy_check};
+use crate::request::{ProxyConfig, RequestPolicy};
+use crate::seeding::SeedingRule;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, ModelTrait, Set};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+/// FlareSolverr 服务地址
+pub const FLARE_SOLVER_URL: Setting<String> = Setting::new("flare_solver_url", String::new);
+/// FlareSolverr 解析超时时间（毫秒）
+pub const FLARE_SOLVER_TIMEOUT: Setting<u64> = Setting::new("flare_solver_timeout", || 60000);
+/// 代理设置
+pub const PROXY: Setting<ProxyConfig> = Setting::new("proxy", ProxyConfig::default);
+/// 代理连接测试地址，需要返回出口 ip
+pub const PROXY_CHECK_URL: Setting<String> =
+    Setting::new("proxy_check_url", || "https://api.ipify.org".to_owned());
+/// 请求限速、重试和超时策略
+pub const REQUEST_POLICY: Setting<RequestPolicy> =
+    Setting::new("request_policy", RequestPolicy::default);
+/// 下载器状态轮询间隔（毫秒）
+pub const DOWNLOAD_WATCH_INTERVAL: Setting<u64> = Setting::new("download_watch_interval", || 60000);
+/// 做种规则
+pub const SEEDING_RULE: Setting<SeedingRule> = Setting::new("seeding_rule", SeedingRule::default);
+/// 质量配置
+pub const QUALITY_PROFILES: Setting<Vec<QualityProfile>> =
+    Setting::new("quality_profiles", Vec::new);
+/// 索引器单次搜索最多请求的页数，索引器未返回结果总数时按此限制
+pub const INDEXER_MAX_PAGES: Setting<u32> = Setting::new("indexer_max_pages", || 5);
+/// TMDb api key
+pub const TMDB_API_KEY: Setting<String> = Setting::new("tmdb_api_key", String::new);
+/// fanart api key
+pub const FANART_API_KEY: Setting<String> = Setting::new("fanart_api_key", String::new);
+
+/// 设置变化通知，传递发生变化的设置 key
+static CHANGES: Lazy<Sender<Arc<str>>> = Lazy::new(|| broadcast::channel(64).0);
+
+/// 订阅设置变化
+pub fn subscribe() -> Receiver<Arc<str>> {
+    CHANGES.subscribe()
+}
+
+/// 设置保存前调用，检查需要立即生效的设置是否可用，返回错误时中止保存
+pub(crate) fn setting_check(key: &str, value: &str) -> Result<()> {
+    if key == PROXY.key() {
+        proxy_check(&PROXY.parse(value)?)?;
+    } else if key == REQUEST_POLICY.key() {
+        policy_check(&REQUEST_POLICY.parse(value)?)?;
+    } else if key == QUALITY_PROFILES.key() {
+        profiles_check(&QUALITY_PROFILES.parse(value)?)?;
+    }
+    Ok(())
+}
+
+/// 设置保存或删除后调用，value 为 None 时表示设置已删除
+pub(crate) fn setting_change(key: &str, value: Option<&str>) -> Result<()> {
+    if key == PROXY.key() {
+        let proxy = value.map(|it| PROXY.parse(it)).transpose()?;
+        proxy_change(&proxy.unwrap_or_else(|| PROXY.default_value()))?;
+    } else if key == REQUEST_POLICY.key() {
+        let policy = value.map(|it| REQUEST_POLICY.parse(it)).transpose()?;
+        policy_change(&policy.unwrap_or_else(|| REQUEST_POLICY.default_value()))?;
+    }
+    // 没有订阅者时会发送失败，直接忽略即可
+    let _ = CHANGES.send(key.into());
+    Ok(())
+}
+
+/// 系统设置项，值以 json 格式存储在 system_config 表中
+pub struct Setting<T> {
+    key: &'static str,
+    default: fn() -> T,
+}
+
+impl<T> Setting<T> {
+    const fn new(key: &'static str, default: fn() -> T) -> Self {
+        Self { key, default }
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    pub fn default_value(&self) -> T {
+        (self.default)()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Setting<T> {
+    fn parse(&self, value: &str) -> Result<T> {
+        let value = serde_json::from_str(value);
+        value.with_context(|| format!("Setting {} has invalid value", self.key))
+    }
+
+    /// 读取设置，未设置时返回默认值
+    pub async fn get(&self) -> Result<T> {
+        let pair = Entity::find_by_id(self.key).one(database()).await;
+        let pair = pair.with_context(|| format!("Query setting {} error", self.key))?;
+        match pair {
+            None => Ok(self.default_value()),
+            Some(it) => self.parse(&it.value),
+        }
+    }
+
+    /// 保存设置，已存在时进行更新
+    pub async fn set(&self, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value)?;
+        let pair = Entity::find_by_id(self.key).one(database()).await;
+        let pair = pair.with_context(|| format!("Query setting {} error", self.key))?;
+        let result = match pair {
+            Some(it) => {
+                let mut model = it.into_active_model();
+                model.value = Set(value);
+                model.update(database()).await
+            }
+            None => {
+                let model = ActiveModel {
+                    key: Set(self.key.to_owned()),
+                    value: Set(value),
+                };
+                model.insert(database()).await
+            }
+        };
+        result.with_context(|| format!("Save setting {} error", self.key))?;
+        Ok(())
+    }
+
+    /// 删除设置，之后读取会返回默认值
+    pub async fn delete(&self) -> Result<()> {
+        let pair = Entity::find_by_id(self.key).one(database()).await;
+        let pair = pair.with_context(|| format!("Query setting {} error", self.key))?;
+        if let Some(it) = pair {
+            let result = it.delete(database()).await;
+            result.with_context(|| format!("Delete setting {} error", self.key))?;
+        }
+        Ok(())
+    }
+}
@@ -34,7 +34,7 @@ impl<'a> TorrentParser<'a> {
     }
 
     pub(super) fn into_files(self) -> Result<Vec<PathBuf>> {
-        if let Some(_) = self.detail.length {
+        if self.detail.length.is_some() {
             Ok(vec![PathBuf::from(self.detail.name)])
         } else if let Some(files) = self.detail.files {
             Ok(files.into_iter().map(TorrentFile::into_path).collect())
@@ -5,6 +5,12 @@ mod magnet;
 use anyhow::Result;
 use std::path::PathBuf;
 
+/// 解析 magnet 链接中的 info hash，混合 hash 使用 v1
+pub fn magnet_hash(magnet: &str) -> Result<String> {
+    let magnet = magnet::Magnet::from_str(magnet)?;
+    Ok(magnet.info_hash().id().to_lowercase())
+}
+
 /// 解析的 torrent 信息
 #[derive(Debug)]
 pub struct Torrent {
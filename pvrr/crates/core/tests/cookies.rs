@@ -0,0 +1,58 @@
+use core::database::load_memory;
+use core::request::save_cookies;
+use core::request::{clear_cookies, direct, flush_cookies, list_cookies, load_cookies};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn cookies_persist_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let url = stub_server(|req| {
+            if req.line.starts_with("GET /login") {
+                return StubResp {
+                    status: 200,
+                    headers: vec![
+                        ("Set-Cookie", "uid=1; Path=/; Max-Age=3600".to_owned()),
+                        ("Set-Cookie", "sid=abc; Path=/".to_owned()),
+                    ],
+                    body: String::new(),
+                };
+            }
+            let cookie = req.header("cookie").unwrap_or_default();
+            let mut cookie: Vec<_> = cookie.split("; ").collect();
+            cookie.sort();
+            assert_eq!(cookie, ["sid=abc", "uid=1"]);
+            (200, String::new()).into()
+        })
+        .await;
+
+        direct().get(format!("{url}/login")).send().await.unwrap();
+        let mut names: Vec<_> = list_cookies("127.0.0.1")
+            .into_iter()
+            .map(|it| (it.name, it.expires.is_some()))
+            .collect();
+        names.sort();
+        assert_eq!(names, [("sid".into(), false), ("uid".into(), true)]);
+
+        // 请求获得的 cookies 会在定时保存时写入文件，没有变化时不会写入
+        let path = std::env::temp_dir().join(format!("pvrr-cookies-{}.json", std::process::id()));
+        flush_cookies(&path).unwrap();
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+        flush_cookies(&path).unwrap();
+        assert!(!path.exists());
+
+        // 会话 cookie 也会保存到文件中
+        save_cookies(&path).unwrap();
+        clear_cookies("127.0.0.1");
+        assert!(list_cookies("127.0.0.1").is_empty());
+
+        load_cookies(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(list_cookies("127.0.0.1").len(), 2);
+        let resp = direct().get(format!("{url}/check")).send().await.unwrap();
+        resp.error_for_status().unwrap();
+    });
+}
@@ -0,0 +1,32 @@
+use core::database::{database, load_memory, EntityTrait};
+use core::entity::download_client::{Category, Entity, Model};
+use sea_orm::{ActiveModelTrait, IntoActiveModel, NotSet};
+use tokio_test::block_on;
+
+#[test]
+fn memory_database_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+        // 重复加载直接返回
+        load_memory().await.unwrap();
+
+        let model = Model {
+            id: 0,
+            cat: Category::Qbittorrent,
+            name: "qbittorrent".to_owned(),
+            url: "http://127.0.0.1:8080".to_owned(),
+            username: None,
+            password: None,
+            download_dir: "/downloads".to_owned(),
+            local_dir: "/data/downloads".to_owned(),
+            category: None,
+            path_mappings: Default::default(),
+        };
+        let mut model = model.into_active_model();
+        model.id = NotSet;
+        let model = model.insert(database()).await.unwrap();
+
+        let found = Entity::find_by_id(model.id).one(database()).await.unwrap();
+        assert_eq!(found, Some(model));
+    });
+}
@@ -0,0 +1,35 @@
+use core::event::{publish, subscribe, DownloadCompleted, FileImported, TorrentAdded};
+use core::finder::{import_file, ImportMode};
+use tokio_test::block_on;
+
+#[test]
+fn event_bus_test() {
+    block_on(async {
+        let mut added = subscribe::<TorrentAdded>();
+        let mut completed = subscribe::<DownloadCompleted>();
+        let mut imported = subscribe::<FileImported>();
+
+        publish(TorrentAdded {
+            downloader: 1,
+            id: "1".to_owned(),
+            info_hash: "abc".to_owned(),
+        });
+        let event = added.recv().await.unwrap();
+        assert_eq!((event.downloader, event.info_hash.as_str()), (1, "abc"));
+        // 不同类型的事件互不影响
+        assert!(completed.try_recv().is_err());
+
+        let dir = std::env::temp_dir().join(format!("pvrr-event-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (from, to) = (dir.join("from.mkv"), dir.join("to.mkv"));
+        std::fs::write(&from, b"pvrr").unwrap();
+        import_file(&from, &to, ImportMode::Copy).unwrap();
+        // 导入失败时不发布事件
+        assert!(import_file(dir.join("none.mkv"), &to, ImportMode::Copy).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let event = imported.recv().await.unwrap();
+        assert_eq!((event.from, event.to), (from, to));
+        assert!(imported.try_recv().is_err());
+    });
+}
@@ -0,0 +1,149 @@
+use core::database::load_memory;
+use core::request::{destroy_solver_sessions, flare_solver, SolverError};
+use core::setting::FLARE_SOLVER_URL;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use test_util::stub_server;
+use tokio_test::block_on;
+
+#[test]
+fn flare_solver_session_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::<Value>::new()));
+        let record = calls.clone();
+        let log = calls.clone();
+        let expired = Arc::new(AtomicBool::new(false));
+        let expire = expired.clone();
+        let url = stub_server(move |req| {
+            // 直接下载 torrent 文件
+            if req.line.starts_with("GET /file.torrent") {
+                assert_eq!(req.header("user-agent"), Some("Mozilla/5.0"));
+                assert_eq!(req.header("cookie"), Some("cf_clearance=solved"));
+                return (200, "d4:infoe".to_owned());
+            }
+            // 直接下载时使用原请求的方法和请求体
+            if req.line.starts_with("POST /download.php?id=1") {
+                assert_eq!(req.body, "passkey=abc");
+                assert_eq!(req.header("cookie"), Some("cf_clearance=solved"));
+                return (200, "d4:infoe".to_owned());
+            }
+
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            record.lock().unwrap().push(param.clone());
+            let target = param["url"].as_str().unwrap_or_default();
+            let resp = match param["cmd"].as_str().unwrap() {
+                // 模拟 FlareSolverr 重启后会话不再存在
+                "request.get" if expire.swap(false, Ordering::SeqCst) => json!({
+                    "status": "error",
+                    "message": "Error: This session does not exist.",
+                }),
+                "request.get" if target.ends_with("/timeout") => json!({
+                    "status": "error",
+                    "message": "Error: Error solving the challenge. Timeout after 60.0 seconds.",
+                }),
+                "request.get" if target.ends_with("/file.torrent") => json!({
+                    "status": "ok",
+                    "message": "Challenge not detected!",
+                    "solution": {
+                        "url": target,
+                        "status": 200,
+                        "cookies": [{ "name": "cf_clearance", "value": "solved", "domain": "127.0.0.1" }],
+                        "userAgent": "Mozilla/5.0"
+                    }
+                }),
+                "request.get" => json!({
+                    "status": "ok",
+                    "message": "Challenge solved!",
+                    "solution": {
+                        "url": "http://tracker.test/torrents",
+                        "status": 200,
+                        "headers": {},
+                        "response": "<html></html>",
+                        "cookies": [{
+                            "name": "cf_clearance",
+                            "value": "solved",
+                            "domain": "tracker.test",
+                            "path": "/",
+                            "expires": -1,
+                            "httpOnly": true,
+                            "secure": false
+                        }],
+                        "userAgent": "Mozilla/5.0"
+                    }
+                }),
+                "request.post" => json!({
+                    "status": "ok",
+                    "message": "Challenge not detected!",
+                    "solution": { "url": target, "status": 200, "userAgent": "Mozilla/5.0" }
+                }),
+                _ => json!({ "status": "ok", "message": "" }),
+            };
+            (200, resp.to_string())
+        })
+        .await;
+        FLARE_SOLVER_URL.set(&url).await.unwrap();
+
+        for _ in 0..2 {
+            let resp = flare_solver().get("http://tracker.test/torrents").send();
+            let resp = resp.await.unwrap().error_for_status().unwrap();
+            assert_eq!(resp.text().await.unwrap(), "<html></html>");
+        }
+        destroy_solver_sessions().await.unwrap();
+
+        let calls = std::mem::take(&mut *calls.lock().unwrap());
+        let cmds: Vec<_> = calls.iter().map(|it| it["cmd"].as_str().unwrap()).collect();
+        assert_eq!(
+            cmds,
+            [
+                "sessions.create",
+                "request.get",
+                "request.get",
+                "sessions.destroy"
+            ]
+        );
+        assert_eq!(calls[1]["session"], "pvrr-tracker.test");
+        assert_eq!(calls[1]["maxTimeout"], 60000);
+        // 第一次请求得到的 cookies 会用于之后的请求
+        assert_eq!(calls[1].get("cookies"), None);
+        assert_eq!(
+            calls[2]["cookies"],
+            json!([{ "name": "cf_clearance", "value": "solved" }])
+        );
+
+        // FlareSolverr 返回错误
+        let resp = flare_solver()
+            .get("http://tracker.test/timeout")
+            .send()
+            .await;
+        let error = resp.err().unwrap();
+        let error = error.downcast_ref::<SolverError>();
+        assert!(matches!(error, Some(SolverError::Timeout(_))));
+
+        // 会话不存在时重新创建会话并重试
+        log.lock().unwrap().clear();
+        expired.store(true, Ordering::SeqCst);
+        let resp = flare_solver().get("http://tracker.test/torrents").send();
+        let resp = resp.await.unwrap().error_for_status().unwrap();
+        assert_eq!(resp.text().await.unwrap(), "<html></html>");
+        let calls = std::mem::take(&mut *log.lock().unwrap());
+        let cmds: Vec<_> = calls.iter().map(|it| it["cmd"].as_str().unwrap()).collect();
+        assert_eq!(cmds, ["request.get", "sessions.create", "request.get"]);
+        assert_eq!(calls[2]["session"], "pvrr-tracker.test");
+
+        // 二进制文件使用解析得到的 cookies 直接下载
+        let resp = flare_solver().get(format!("{url}/file.torrent")).send();
+        let bytes = resp.await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"d4:infoe");
+
+        let resp = flare_solver()
+            .post(format!("{url}/download.php"))
+            .query(&[("id", "1")])
+            .form(&[("passkey", "abc")])
+            .send();
+        let bytes = resp.await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"d4:infoe");
+    });
+}
@@ -0,0 +1,72 @@
+use core::database::{database, load_memory};
+use core::entity::index_client::{ActiveModel, Category, Entity};
+use core::request::direct;
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+fn custom_client(url: &str, cookies: &str) -> ActiveModel {
+    ActiveModel {
+        cat: Set(Category::Custom),
+        name: Set("custom".to_owned()),
+        url: Set(url.to_owned()),
+        use_proxy: Set(false),
+        username: Set(None),
+        password: Set(Some(cookies.to_owned())),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn custom_cookies_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let url = stub_server(|req| {
+            // cookies 顺序不固定
+            let cookie = req.header("cookie").unwrap_or_default();
+            let mut cookie: Vec<_> = cookie.split("; ").collect();
+            cookie.sort();
+            assert_eq!(cookie, ["pass=abc", "uid=1"]);
+            StubResp::from((200, String::new()))
+        })
+        .await;
+
+        // ip 地址和端口均可使用
+        let client = custom_client(&url, "uid=1; pass=abc;");
+        let client = client.insert(database()).await.unwrap();
+        let resp = direct().get(format!("{url}/search")).send().await.unwrap();
+        resp.error_for_status().unwrap();
+
+        // 错误的设置不会保存
+        let err = custom_client("not a url", "uid=1").insert(database()).await;
+        assert!(err.unwrap_err().to_string().contains("Invalid cookies url"));
+        let err = custom_client("data:text/plain,pvrr", "uid=1")
+            .insert(database())
+            .await;
+        assert!(err.unwrap_err().to_string().contains("no host"));
+        let err = custom_client(&url, "uid=1; pass").insert(database()).await;
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid cookie `pass`"));
+        let err = custom_client(&url, "u id=1").insert(database()).await;
+        assert!(err.is_err());
+
+        // 仅更新部分字段时，使用已保存的值检查，错误的设置不会保存
+        let cookies_only = ActiveModel {
+            id: Set(client.id),
+            password: Set(Some("uid=1; pass".to_owned())),
+            ..Default::default()
+        };
+        assert!(cookies_only.update(database()).await.is_err());
+        let url_only = ActiveModel {
+            id: Set(client.id),
+            url: Set("not a url".to_owned()),
+            ..Default::default()
+        };
+        assert!(url_only.update(database()).await.is_err());
+        let saved = Entity::find_by_id(client.id).one(database()).await.unwrap();
+        assert_eq!(saved, Some(client));
+    });
+}
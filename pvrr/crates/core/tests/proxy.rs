@@ -0,0 +1,47 @@
+use core::database::load_memory;
+use core::request::{load, proxy, proxy_test, ProxyConfig};
+use core::setting::{PROXY, PROXY_CHECK_URL};
+use test_util::stub_server;
+use tokio_test::block_on;
+
+#[test]
+fn proxy_config_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+        load().await.unwrap();
+        assert!(proxy().is_err());
+
+        // 测试服务作为 http 代理，请求行为完整的目标地址
+        let url = stub_server(|req| {
+            assert_eq!(req.line, "GET http://ip.test/ HTTP/1.1");
+            let auth = req.header("proxy-authorization");
+            assert_eq!(auth, Some("Basic dXNlcjpwYXNz"));
+            (200, "203.0.113.7\n".to_owned())
+        })
+        .await;
+        PROXY_CHECK_URL
+            .set(&"http://ip.test/".to_owned())
+            .await
+            .unwrap();
+
+        let config = ProxyConfig {
+            url,
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        PROXY.set(&config).await.unwrap();
+        assert_eq!(proxy_test().await.unwrap(), "203.0.113.7");
+
+        // 无效的代理设置不会保存
+        let invalid = ProxyConfig {
+            url: "ftp://127.0.0.1:21".to_owned(),
+            ..ProxyConfig::default()
+        };
+        assert!(PROXY.set(&invalid).await.is_err());
+        assert_eq!(PROXY.get().await.unwrap(), config);
+        assert_eq!(proxy_test().await.unwrap(), "203.0.113.7");
+
+        PROXY.delete().await.unwrap();
+        assert!(proxy().is_err());
+    });
+}
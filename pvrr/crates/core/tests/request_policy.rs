@@ -0,0 +1,101 @@
+use core::database::load_memory;
+use core::request::{direct, RequestPolicy, StatusCode};
+use core::setting::REQUEST_POLICY;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use test_util::{stub_server, StubResp};
+use tokio::net::TcpListener;
+use tokio_test::block_on;
+
+#[test]
+fn request_policy_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+        let policy = RequestPolicy {
+            burst: 2,
+            interval: 200,
+            max_retries: 2,
+            retry_delay: 50,
+            max_retry_delay: 1500,
+            timeout: 0,
+        };
+        REQUEST_POLICY.set(&policy).await.unwrap();
+
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = stub_server(move |req| {
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let path = req.line.split(' ').nth(1).unwrap_or_default();
+            let retry_after = |value: &str| StubResp {
+                status: 429,
+                headers: vec![("Retry-After", value.to_owned())],
+                body: String::new(),
+            };
+            match path {
+                "/unstable" if count < 3 => (503, String::new()).into(),
+                "/busy" if count < 2 => retry_after("1"),
+                "/banned" => retry_after("3600"),
+                "/broken" => (500, String::new()).into(),
+                _ => (200, String::new()).into(),
+            }
+        })
+        .await;
+        let request = |path: &str| {
+            count.store(0, Ordering::SeqCst);
+            direct().get(format!("{url}{path}")).send()
+        };
+
+        // 同一站点超过突发请求数后按间隔发出
+        let start = Instant::now();
+        for _ in 0..4 {
+            request("/").await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        REQUEST_POLICY
+            .set(&RequestPolicy {
+                interval: 0,
+                ..policy
+            })
+            .await
+            .unwrap();
+
+        // 5xx 指数退避重试
+        let resp = request("/unstable").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // 超过最大重试次数后返回最后一次响应
+        let resp = request("/broken").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // POST 请求不一定是幂等的，不进行重试
+        count.store(0, Ordering::SeqCst);
+        let resp = direct()
+            .post(format!("{url}/unstable"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // 遵循 Retry-After
+        let start = Instant::now();
+        let resp = request("/busy").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        // Retry-After 过长时不再重试
+        let resp = request("/banned").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // 请求超时
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let req = direct().get(format!("http://{addr}/"));
+        let resp = req.timeout(Duration::from_millis(200)).send().await;
+        assert!(resp.is_err());
+    });
+}
@@ -0,0 +1,28 @@
+use core::database::load_memory;
+use core::setting::{subscribe, FLARE_SOLVER_URL};
+use tokio_test::block_on;
+
+#[test]
+fn setting_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+        let mut changes = subscribe();
+        let key = FLARE_SOLVER_URL.key();
+
+        assert_eq!(FLARE_SOLVER_URL.get().await.unwrap(), "");
+
+        let url = "http://127.0.0.1:8191".to_owned();
+        FLARE_SOLVER_URL.set(&url).await.unwrap();
+        assert_eq!(FLARE_SOLVER_URL.get().await.unwrap(), url);
+        assert_eq!(&*changes.recv().await.unwrap(), key);
+
+        let url = "http://localhost:8191".to_owned();
+        FLARE_SOLVER_URL.set(&url).await.unwrap();
+        assert_eq!(FLARE_SOLVER_URL.get().await.unwrap(), url);
+        assert_eq!(&*changes.recv().await.unwrap(), key);
+
+        FLARE_SOLVER_URL.delete().await.unwrap();
+        assert_eq!(FLARE_SOLVER_URL.get().await.unwrap(), "");
+        assert_eq!(&*changes.recv().await.unwrap(), key);
+    });
+}
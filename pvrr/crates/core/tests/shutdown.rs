@@ -0,0 +1,29 @@
+use core::request::direct;
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn shutdown_test() {
+    block_on(async {
+        let dir = std::env::temp_dir().join(format!("pvrr-shutdown-{}", std::process::id()));
+        std::env::set_var("DATA_PATH", &dir);
+        // 重复加载时替换之前的定时保存任务
+        core::load().await.unwrap();
+        core::load().await.unwrap();
+
+        let url = stub_server(|_| StubResp {
+            status: 200,
+            headers: vec![("Set-Cookie", "uid=1; Path=/; Max-Age=3600".to_owned())],
+            body: String::new(),
+        })
+        .await;
+        direct().get(format!("{url}/login")).send().await.unwrap();
+
+        // 退出时保存定时保存后有变化的 cookies
+        let path = dir.join("cookies.json");
+        assert!(!path.exists());
+        core::shutdown().unwrap();
+        assert!(path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    });
+}
@@ -1,11 +1,16 @@
-use crate::{DownloadItem, ItemStatus};
-use anyhow::{bail, Result};
+use crate::mapping::PathMapper;
+use crate::{
+    eta, message, progress, ratio, remove_path, DownloadFile, DownloadItem, ItemStatus,
+    TorrentLimits,
+};
+use anyhow::{bail, Context, Result};
 use base64::Engine;
 use core::entity::download_client::Model;
 use core::request::direct;
+use serde::de::Error;
 use serde::de::{DeserializeOwned, IgnoredAny};
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
 use std::ffi::OsStr;
 use std::path::Path;
 
@@ -29,21 +34,53 @@ enum Response<T> {
     Error { error: RespError },
 }
 
+/// aria2 返回的数字均为字符串，同时兼容数字类型
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Str(String),
+        Num(u64),
+    }
+    match Number::deserialize(deserializer)? {
+        Number::Str(it) => it.parse().map_err(D::Error::custom),
+        Number::Num(it) => Ok(it),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct DownloadFileInfo {
     path: String,
+    #[serde(default, deserialize_with = "number")]
+    length: u64,
+    #[serde(rename = "completedLength", default, deserialize_with = "number")]
+    completed: u64,
 }
 
 #[derive(Debug, Deserialize)]
 struct DownloadStatus {
     gid: String,
-    #[serde(rename = "totalLength")]
+    #[serde(rename = "totalLength", deserialize_with = "number")]
     total: u64,
-    #[serde(rename = "completedLength")]
+    #[serde(rename = "completedLength", deserialize_with = "number")]
     completed: u64,
+    #[serde(rename = "uploadLength", default, deserialize_with = "number")]
+    uploaded: u64,
+    #[serde(rename = "downloadSpeed", default, deserialize_with = "number")]
+    download_speed: u64,
+    #[serde(rename = "uploadSpeed", default, deserialize_with = "number")]
+    upload_speed: u64,
+    #[serde(rename = "errorMessage")]
+    error_message: Option<String>,
     status: String,
     dir: String,
     files: Vec<DownloadFileInfo>,
+    /// BitTorrent 下载的 info hash
+    #[serde(rename = "infoHash")]
+    info_hash: Option<String>,
+    /// magnet 元数据下载完成后，实际下载使用新的 gid
+    #[serde(rename = "followedBy", default)]
+    followed_by: Vec<String>,
 }
 
 impl DownloadStatus {
@@ -59,11 +96,26 @@ impl DownloadStatus {
         file_it.next().map(|it| it.as_os_str())
     }
 
-    fn into_item(self, id: u32, local: &str) -> DownloadItem {
-        let path = self.name().map(|it| Path::new(local).join(it));
+    /// magnet 元数据下载，完成后由新的下载任务继续下载
+    fn is_metadata(&self) -> bool {
+        let file = self.files.first().map(|it| it.path.as_str());
+        file.is_none_or(|it| it.is_empty() || it.starts_with("[METADATA]"))
+    }
+
+    /// BitTorrent 下载使用 info hash 作为 id，保证 magnet 元数据下载和实际下载 id 一致
+    fn id(&self) -> &str {
+        self.info_hash.as_deref().unwrap_or(&self.gid)
+    }
+
+    fn into_item(self, id: u32, paths: &PathMapper) -> DownloadItem {
+        let metadata = self.is_metadata();
+        let path = self.name().map(|it| Path::new(&self.dir).join(it));
+        let path = path.and_then(|it| paths.local(it));
+        let path = path.filter(|_| !metadata);
         let downloader = id;
-        let id = self.gid;
+        let id = self.id().to_owned();
         let status = match self.status.as_str() {
+            "active" | "waiting" | "paused" if metadata => ItemStatus::Downloading,
             _ if path.is_none() => ItemStatus::Error,
             _ if self.total == 0 => ItemStatus::Error,
             "active" | "paused" if self.total == self.completed => ItemStatus::Downloaded,
@@ -71,12 +123,32 @@ impl DownloadStatus {
             "complete" => ItemStatus::Complete,
             _ => ItemStatus::Error,
         };
-        DownloadItem {
-            downloader,
-            id,
-            status,
-            path: path.unwrap_or_default(),
+
+        let mut item = DownloadItem::new(downloader, id, status, path.unwrap_or_default());
+        if metadata {
+            return item;
         }
+        item.progress = progress(self.completed, self.total);
+        item.size = self.total;
+        item.downloaded = self.completed;
+        item.uploaded = self.uploaded;
+        item.download_speed = self.download_speed;
+        item.upload_speed = self.upload_speed;
+        item.ratio = ratio(self.uploaded, self.completed);
+        item.eta = eta(
+            self.total - self.completed.min(self.total),
+            self.download_speed,
+        );
+        item.message = self.error_message.and_then(message);
+        let files = self.files.into_iter().filter_map(|it| {
+            Some(DownloadFile {
+                path: paths.local(&it.path)?,
+                size: it.length,
+                progress: progress(it.completed, it.length),
+            })
+        });
+        item.files = files.collect();
+        item
     }
 }
 
@@ -85,7 +157,7 @@ pub struct Client {
     url: String,
     secret: Option<String>,
     download_dir: String,
-    local_dir: String,
+    paths: PathMapper,
 }
 
 impl Client {
@@ -96,7 +168,7 @@ impl Client {
     pub(crate) async fn download(&self, torrent: &[u8]) -> Result<DownloadItem> {
         let id = self.add_torrent(torrent, &self.download_dir).await?;
         let status = self.tell_status(&id).await?;
-        Ok(status.into_item(self.id, &self.local_dir))
+        Ok(status.into_item(self.id, &self.paths))
     }
 
     pub(crate) async fn download_list(&self) -> Result<Vec<DownloadItem>> {
@@ -107,12 +179,105 @@ impl Client {
         ];
         let list = vec.into_iter();
         let list = list.flatten();
-        let list = list.map(|it| it.into_item(self.id, &self.local_dir));
+        // 已完成的 magnet 元数据下载由后续下载任务替代
+        let list = list.filter(|it| it.followed_by.is_empty());
+        let list = list.map(|it| it.into_item(self.id, &self.paths));
         Ok(list.collect())
     }
+
+    pub(crate) async fn download_magnet(&self, magnet: &str) -> Result<DownloadItem> {
+        let dir = self.download_dir.as_str();
+        let id: String = self
+            .rpc("aria2.addUri", |param| {
+                param.push(json!([magnet]));
+                param.push(json!({ "dir": dir }))
+            })
+            .await?;
+        let status = self.tell_status(&id).await?;
+        Ok(status.into_item(self.id, &self.paths))
+    }
+
+    pub(crate) async fn pause(&self, id: &str) -> Result<()> {
+        self.gid_rpc("aria2.pause", &self.gid(id).await?).await
+    }
+
+    pub(crate) async fn resume(&self, id: &str) -> Result<()> {
+        self.gid_rpc("aria2.unpause", &self.gid(id).await?).await
+    }
+
+    /// aria2 删除任务时不会删除文件，需要删除时直接删除本地文件
+    pub(crate) async fn remove(&self, id: &str, delete_data: bool) -> Result<()> {
+        let id = self.gid(id).await?;
+        let id = id.as_str();
+        let status = self.tell_status(id).await?;
+        if matches!(status.status.as_str(), "active" | "waiting" | "paused") {
+            self.gid_rpc("aria2.forceRemove", id).await?;
+            // 任务停止后才能删除下载结果，未停止时会在下次清理时删除
+            let _ = self.gid_rpc("aria2.removeDownloadResult", id).await;
+        } else {
+            self.gid_rpc("aria2.removeDownloadResult", id).await?;
+        }
+
+        let path = status.name().map(|it| Path::new(&status.dir).join(it));
+        let path = path.and_then(|it| self.paths.local(it));
+        if let Some(path) = path.filter(|_| delete_data) {
+            let mut control = path.clone().into_os_string();
+            control.push(".aria2");
+            remove_path(Path::new(&control))?;
+            remove_path(&path)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn recheck(&self, _id: &str) -> Result<()> {
+        bail!("aria2 unsupported recheck torrent")
+    }
+
+    pub(crate) async fn set_category(&self, _id: &str, _category: &str) -> Result<()> {
+        bail!("aria2 unsupported torrent category")
+    }
+
+    pub(crate) async fn set_limits(&self, id: &str, limits: &TorrentLimits) -> Result<()> {
+        let id = self.gid(id).await?;
+        let mut option = Map::new();
+        if let Some(upload) = limits.upload {
+            option.insert("max-upload-limit".into(), upload.to_string().into());
+        }
+        if let Some(download) = limits.download {
+            option.insert("max-download-limit".into(), download.to_string().into());
+        }
+        if let Some(ratio) = limits.ratio {
+            option.insert("seed-ratio".into(), ratio.to_string().into());
+        }
+        let _: IgnoredAny = self
+            .rpc("aria2.changeOption", |param| {
+                param.push(id.into());
+                param.push(option.into());
+            })
+            .await?;
+        Ok(())
+    }
 }
 
 impl Client {
+    /// 下载项 id 为 info hash 时查找对应的 gid
+    async fn gid(&self, id: &str) -> Result<String> {
+        let is_hash = id.len() == 40 && id.bytes().all(|it| it.is_ascii_hexdigit());
+        if !is_hash {
+            return Ok(id.to_owned());
+        }
+        let vec = [
+            self.tell_waiting().await?,
+            self.tell_active().await?,
+            self.tell_stopped().await?,
+        ];
+        let mut list = vec.into_iter().flatten();
+        let status = list.find(|it| it.followed_by.is_empty() && it.id() == id);
+        status
+            .map(|it| it.gid)
+            .with_context(|| format!("Can't find aria2 download {id}"))
+    }
+
     async fn get_version(&self) -> Result<()> {
         let _: IgnoredAny = self.rpc("aria2.getVersion", |_| {}).await?;
         Ok(())
@@ -132,6 +297,12 @@ impl Client {
             .await
     }
 
+    /// 只需要 gid 参数的方法
+    async fn gid_rpc(&self, method: &'static str, id: &str) -> Result<()> {
+        let _: IgnoredAny = self.rpc(method, |param| param.push(id.into())).await?;
+        Ok(())
+    }
+
     async fn tell_active(&self) -> Result<Vec<DownloadStatus>> {
         self.rpc("aria2.tellActive", |_| {}).await
     }
@@ -155,12 +326,12 @@ impl Client {
 
 impl Client {
     fn secret(&self) -> Option<&str> {
-        self.secret.as_ref().map(String::as_str)
+        self.secret.as_deref()
     }
 
     async fn rpc<F, T>(&self, method: &'static str, param_fn: F) -> Result<T>
     where
-        F: FnOnce(&mut Vec<Value>) -> (),
+        F: FnOnce(&mut Vec<Value>),
         T: DeserializeOwned,
     {
         let mut param = Vec::with_capacity(3);
@@ -170,12 +341,15 @@ impl Client {
         }
         param_fn(&mut param);
 
-        let req = direct().post(&self.url).json(&Request {
-            jsonrpc: "2.0",
-            id: "pvr-rpc-call",
-            method,
-            params: param.into(),
-        });
+        let req = direct()
+            .without_rate_limit()
+            .post(&self.url)
+            .json(&Request {
+                jsonrpc: "2.0",
+                id: "pvr-rpc-call",
+                method,
+                params: param.into(),
+            });
         // aira2 鉴权失败需要用户提供新的 secure
         // 不需要重复多次获取 session 等信息
         let resp = req.send().await?;
@@ -190,12 +364,13 @@ impl Client {
 
 impl From<Model> for Client {
     fn from(value: Model) -> Self {
+        let paths = PathMapper::from(&value);
         Self {
             id: value.id,
             url: value.url,
             secret: value.password,
             download_dir: value.download_dir,
-            local_dir: value.local_dir,
+            paths,
         }
     }
 }
@@ -0,0 +1,438 @@
+use crate::mapping::PathMapper;
+use crate::{
+    message, timestamp, DownloadFile, DownloadItem, ItemStatus, TorrentLimits, DEFAULT_CATEGORY,
+};
+use anyhow::{ensure, Context, Result};
+use base64::Engine;
+use core::entity::download_client::Model;
+use core::request::direct;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 未登录时返回的错误码
+const NOT_AUTHENTICATED: i32 = 1;
+/// 标签已存在时的错误信息
+const LABEL_EXISTS: &str = "Label already exists";
+/// 添加已存在的 torrent 时的错误信息
+const ALREADY_IN_SESSION: &str = "already in session";
+
+static TORRENT_FIELDS: [&str; 20] = [
+    "name",
+    "save_path",
+    "state",
+    "progress",
+    "total_wanted",
+    "total_done",
+    "total_uploaded",
+    "download_payload_rate",
+    "upload_payload_rate",
+    "ratio",
+    "eta",
+    "seeding_time",
+    "time_added",
+    "completed_time",
+    "message",
+    "tracker_status",
+    "is_finished",
+    "label",
+    "files",
+    "file_progress",
+];
+
+#[derive(Debug, Serialize)]
+struct Request {
+    method: &'static str,
+    params: Value,
+    id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RespError {
+    message: String,
+    code: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    result: Option<T>,
+    error: Option<RespError>,
+}
+
+/// deluged 信息，依次为 id、地址、端口、用户名
+#[derive(Debug, Deserialize)]
+struct HostInfo(String, IgnoredAny, IgnoredAny, IgnoredAny);
+
+#[derive(Debug, Deserialize)]
+struct TorrentInfo {
+    name: String,
+    #[serde(default)]
+    save_path: String,
+    state: String,
+    /// 下载进度，0 ~ 100
+    progress: f64,
+    #[serde(default)]
+    total_wanted: u64,
+    #[serde(default)]
+    total_done: u64,
+    #[serde(default)]
+    total_uploaded: u64,
+    /// 速度单位为字节/秒
+    #[serde(default)]
+    download_payload_rate: u64,
+    #[serde(default)]
+    upload_payload_rate: u64,
+    /// 没有分享率时为 -1
+    #[serde(default)]
+    ratio: f64,
+    /// 剩余秒数，0 表示无法估计
+    #[serde(default)]
+    eta: f64,
+    #[serde(default)]
+    seeding_time: f64,
+    #[serde(default)]
+    time_added: f64,
+    /// 未完成时为 0 或 -1
+    #[serde(default)]
+    completed_time: f64,
+    /// 下载错误信息，没有错误时为 "OK"
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    tracker_status: String,
+    is_finished: bool,
+    #[serde(default)]
+    files: Vec<TorrentFile>,
+    /// 各文件下载进度，0 ~ 1，与 files 顺序一致
+    #[serde(default)]
+    file_progress: Vec<f64>,
+}
+
+/// torrent 文件信息，path 为相对于 save_path 的路径
+#[derive(Debug, Deserialize)]
+struct TorrentFile {
+    path: String,
+    size: u64,
+}
+
+impl TorrentInfo {
+    fn into_item(self, id: u32, hash: String, paths: &PathMapper) -> DownloadItem {
+        let path = paths.local(Path::new(&self.save_path).join(&self.name));
+        let downloader = id;
+        let status = match self.state.as_str() {
+            _ if path.is_none() => ItemStatus::Error,
+            "Error" => ItemStatus::Error,
+            _ if self.progress < 100.0 => ItemStatus::Downloading,
+            "Checking" | "Allocating" | "Moving" => ItemStatus::Downloading,
+            // 达到做种分享率后会暂停
+            "Paused" if self.is_finished => ItemStatus::Complete,
+            "Seeding" | "Queued" | "Paused" => ItemStatus::Downloaded,
+            _ => ItemStatus::Error,
+        };
+
+        let path = path.unwrap_or_default();
+        let mut item = DownloadItem::new(downloader, hash, status, path);
+        item.progress = self.progress / 100.0;
+        item.size = self.total_wanted;
+        item.downloaded = self.total_done;
+        item.uploaded = self.total_uploaded;
+        item.download_speed = self.download_payload_rate;
+        item.upload_speed = self.upload_payload_rate;
+        item.ratio = self.ratio.max(0.0);
+        let eta = Some(self.eta).filter(|it| *it > 0.0);
+        item.eta = eta.map(Duration::from_secs_f64);
+        let seeding_time = Some(self.seeding_time).filter(|it| *it > 0.0);
+        item.seeding_time = seeding_time.map(Duration::from_secs_f64);
+        item.added_at = timestamp(self.time_added as i64);
+        item.completed_at = timestamp(self.completed_time as i64);
+        item.message = match self.state.as_str() {
+            "Error" => message(self.message),
+            _ => message(self.tracker_status),
+        };
+        let dir = Path::new(&self.save_path);
+        let progress = self.file_progress.into_iter().chain(std::iter::repeat(0.0));
+        let files = self.files.into_iter().zip(progress);
+        let files = files.filter_map(|(it, progress)| {
+            Some(DownloadFile {
+                path: paths.local(dir.join(&it.path))?,
+                size: it.size,
+                progress,
+            })
+        });
+        item.files = files.collect();
+        item
+    }
+}
+
+pub struct Client {
+    id: u32,
+    url: String,
+    password: Option<String>,
+    download_dir: String,
+    paths: PathMapper,
+    category: Arc<str>,
+}
+
+impl Client {
+    pub(crate) async fn connect_test(&self) -> Result<()> {
+        self.login().await?;
+        self.connect().await
+    }
+
+    /// 使用 core 方法添加，web.add_torrents 需要先上传 torrent 文件且不返回 hash
+    pub(crate) async fn download(&self, torrent: &[u8], hash: &str) -> Result<DownloadItem> {
+        let torrent = base64::engine::general_purpose::STANDARD.encode(torrent);
+        let options = self.add_options();
+        let added = self
+            .rpc("core.add_torrent_file", |param| {
+                param.push("pvrr.torrent".into());
+                param.push(torrent.into());
+                param.push(options);
+            })
+            .await;
+        self.added(added, hash).await
+    }
+
+    pub(crate) async fn download_magnet(&self, magnet: &str, hash: &str) -> Result<DownloadItem> {
+        let options = self.add_options();
+        let added = self
+            .rpc("core.add_torrent_magnet", |param| {
+                param.push(magnet.into());
+                param.push(options);
+            })
+            .await;
+        self.added(added, hash).await
+    }
+
+    pub(crate) async fn download_list(&self) -> Result<Vec<DownloadItem>> {
+        let filter = json!({ "label": self.category.to_lowercase() });
+        let list = self.torrents_status(filter).await?.into_iter();
+        let list = list.map(|(hash, it)| it.into_item(self.id, hash, &self.paths));
+        Ok(list.collect())
+    }
+
+    pub(crate) async fn pause(&self, hash: &str) -> Result<()> {
+        self.rpc_without_resp("core.pause_torrent", |param| param.push(json!([hash])))
+            .await
+    }
+
+    pub(crate) async fn resume(&self, hash: &str) -> Result<()> {
+        self.rpc_without_resp("core.resume_torrent", |param| param.push(json!([hash])))
+            .await
+    }
+
+    pub(crate) async fn remove(&self, hash: &str, remove_data: bool) -> Result<()> {
+        self.rpc_without_resp("core.remove_torrent", |param| {
+            param.push(hash.into());
+            param.push(remove_data.into());
+        })
+        .await
+    }
+
+    pub(crate) async fn recheck(&self, hash: &str) -> Result<()> {
+        self.rpc_without_resp("core.force_recheck", |param| param.push(json!([hash])))
+            .await
+    }
+
+    /// 使用 Label 插件设置标签，标签只能为小写
+    /// Deluge 每个下载项只有一个标签，该标签用于跟踪下载项，因此不能设置其他分类
+    pub(crate) async fn set_category(&self, hash: &str, category: &str) -> Result<()> {
+        ensure!(
+            category.eq_ignore_ascii_case(&self.category),
+            "Deluge label is used to track downloads, can't set category `{category}`"
+        );
+        self.set_label(hash).await
+    }
+
+    pub(crate) async fn set_limits(&self, hash: &str, limits: &TorrentLimits) -> Result<()> {
+        // 速度单位为 KiB/s，-1 表示不限制
+        let kib = |it: u64| match it {
+            0 => -1,
+            it => it.div_ceil(1024) as i64,
+        };
+        let mut options = serde_json::Map::new();
+        if let Some(upload) = limits.upload {
+            options.insert("max_upload_speed".into(), kib(upload).into());
+        }
+        if let Some(download) = limits.download {
+            options.insert("max_download_speed".into(), kib(download).into());
+        }
+        if let Some(ratio) = limits.ratio {
+            options.insert("stop_at_ratio".into(), true.into());
+            options.insert("stop_ratio".into(), ratio.into());
+        }
+        self.rpc_without_resp("core.set_torrent_options", |param| {
+            param.push(json!([hash]));
+            param.push(options.into());
+        })
+        .await
+    }
+}
+
+impl Client {
+    fn add_options(&self) -> Value {
+        json!({ "download_location": self.download_dir })
+    }
+
+    /// 设置用于跟踪下载项的标签，Deluge 标签只能为小写，
+    /// 未启用 Label 插件时返回错误，否则下载项不会出现在下载列表中
+    async fn set_label(&self, hash: &str) -> Result<()> {
+        let label = self.category.to_lowercase();
+        let add = self
+            .rpc_without_resp("label.add", |param| param.push(label.as_str().into()))
+            .await;
+        match add {
+            Err(e) if is_error(&e, LABEL_EXISTS) => {}
+            add => add.context("Deluge label plugin error")?,
+        }
+        self.rpc_without_resp("label.set_torrent", |param| {
+            param.push(hash.into());
+            param.push(label.as_str().into());
+        })
+        .await
+    }
+
+    /// 设置新添加 torrent 的标签并获取下载项信息，
+    /// 已存在相同的 torrent 时返回已有的下载项，不修改其标签
+    async fn added(&self, added: Result<Option<String>>, hash: &str) -> Result<DownloadItem> {
+        // 部分 Deluge 1.x 版本添加成功时不返回 hash，已存在时 Deluge 2.x 返回错误
+        let hash = match added {
+            Ok(added) => {
+                let hash = added.unwrap_or_else(|| hash.to_owned());
+                self.set_label(&hash).await?;
+                hash
+            }
+            Err(e) if is_error(&e, ALREADY_IN_SESSION) => hash.to_owned(),
+            Err(e) => return Err(e),
+        };
+        let filter = json!({ "id": [hash] });
+        let mut list = self.torrents_status(filter).await?;
+        let info = list.remove(&hash).context("Can't find torrent.")?;
+        Ok(info.into_item(self.id, hash, &self.paths))
+    }
+
+    async fn torrents_status(&self, filter: Value) -> Result<HashMap<String, TorrentInfo>> {
+        self.rpc("core.get_torrents_status", |param| {
+            param.push(filter);
+            param.push(TORRENT_FIELDS.as_slice().into());
+        })
+        .await
+    }
+
+    async fn login(&self) -> Result<()> {
+        let password = self.password.as_deref().unwrap_or_default();
+        let req = Request {
+            method: "auth.login",
+            params: json!([password]),
+            id: 0,
+        };
+        let success: bool = self.send(&req).await?;
+        ensure!(success, "Deluge login failed.");
+        Ok(())
+    }
+
+    /// Web UI 未连接 deluged 时连接第一个 deluged
+    async fn connect(&self) -> Result<()> {
+        let connected: bool = self.rpc("web.connected", |_| {}).await?;
+        if connected {
+            return Ok(());
+        }
+        let hosts: Vec<HostInfo> = self.rpc("web.get_hosts", |_| {}).await?;
+        let host = hosts.first().context("Deluge has no daemon host.")?;
+        let host = host.0.as_str();
+        let _: IgnoredAny = self
+            .rpc("web.connect", |param| param.push(host.into()))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    async fn rpc_without_resp<F>(&self, method: &'static str, param_fn: F) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<Value>),
+    {
+        let _: IgnoredAny = self.rpc(method, param_fn).await?;
+        Ok(())
+    }
+
+    async fn rpc<F, T>(&self, method: &'static str, param_fn: F) -> Result<T>
+    where
+        F: FnOnce(&mut Vec<Value>),
+        T: DeserializeOwned,
+    {
+        let mut param = Vec::with_capacity(3);
+        param_fn(&mut param);
+        let req = Request {
+            method,
+            params: param.into(),
+            id: 1,
+        };
+
+        // 未登录或登录过期时，先登录后再次请求，登录状态保存在 cookies 中
+        match self.send(&req).await {
+            Err(e) if is_not_authenticated(&e) => {
+                self.login().await?;
+                self.send(&req).await
+            }
+            resp => resp,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, req: &Request) -> Result<T> {
+        let url = format!("{}/json", self.url.trim_end_matches('/'));
+        let req = direct().without_rate_limit().post(url).json(req);
+        let resp = req.send().await?.error_for_status()?;
+        let resp: Response<T> = resp.json().await?;
+        match (resp.result, resp.error) {
+            (_, Some(error)) => Err(DelugeError(error).into()),
+            (Some(result), None) => Ok(result),
+            // 部分方法返回 null，此时使用 null 进行反序列化
+            (None, None) => Ok(serde_json::from_value(Value::Null)?),
+        }
+    }
+}
+
+/// Deluge 返回的错误
+#[derive(Debug)]
+struct DelugeError(RespError);
+
+impl std::fmt::Display for DelugeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.message)
+    }
+}
+
+impl std::error::Error for DelugeError {}
+
+/// 错误信息是否包含所给内容，Deluge 的错误码不区分具体错误
+fn is_error(e: &anyhow::Error, message: &str) -> bool {
+    let error = e.downcast_ref::<DelugeError>();
+    error.is_some_and(|it| it.0.message.contains(message))
+}
+
+fn is_not_authenticated(e: &anyhow::Error) -> bool {
+    let error = e.downcast_ref::<DelugeError>();
+    error.is_some_and(|it| it.0.code == NOT_AUTHENTICATED)
+}
+
+impl From<Model> for Client {
+    fn from(value: Model) -> Self {
+        let paths = PathMapper::from(&value);
+        Self {
+            id: value.id,
+            url: value.url,
+            password: value.password,
+            download_dir: value.download_dir,
+            paths,
+            category: value
+                .category
+                .map(|it| it.into())
+                .unwrap_or(DEFAULT_CATEGORY.clone()),
+        }
+    }
+}
@@ -1,31 +1,35 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
 mod aira2;
+mod deluge;
+mod mapping;
 mod qbittorrent;
+mod rtorrent;
+mod seeding;
 mod transmission;
+mod watcher;
 
 use anyhow::{Context, Result};
 use core::database::{database, EntityTrait};
 use core::entity::download_client::{Category, Entity, Model};
+use core::event::{publish, TorrentAdded};
+use core::torrent::magnet_hash;
 use once_cell::sync::Lazy;
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use core::entity::download_item::ItemStatus;
+pub use seeding::{check_seeding, check_seeding_with, link_source, SeedDecision, SeedReport};
+pub use watcher::{spawn_watcher, watch_once};
 
 /// 下载器默认类别
 static DEFAULT_CATEGORY: Lazy<Arc<str>> = Lazy::new(|| "pvrr".into());
-
-/// 下载状态
-pub enum ItemStatus {
-    /// 下载中
-    Downloading,
-    /// 下载完成，做种中
-    Downloaded,
-    /// 下载完成，做种完成
-    Complete,
-    /// 下载错误
-    Error,
-}
+/// 需要单独获取文件列表的下载器，获取下载项列表时同时发送的最大请求数
+const FILES_CONCURRENCY: usize = 4;
 
 /// 下载项信息
+#[derive(Clone, Debug)]
 pub struct DownloadItem {
     /// 下载器 id
     pub downloader: u32,
@@ -35,16 +39,116 @@ pub struct DownloadItem {
     pub status: ItemStatus,
     /// 下载项路径
     pub path: PathBuf,
+    /// 下载进度，0 ~ 1
+    pub progress: f64,
+    /// 需要下载的总大小（字节）
+    pub size: u64,
+    /// 已下载大小（字节）
+    pub downloaded: u64,
+    /// 已上传大小（字节）
+    pub uploaded: u64,
+    /// 下载速度（字节/秒）
+    pub download_speed: u64,
+    /// 上传速度（字节/秒）
+    pub upload_speed: u64,
+    /// 分享率
+    pub ratio: f64,
+    /// 预计剩余下载时间，下载完成或无法估计时为 None
+    pub eta: Option<Duration>,
+    /// 做种时间
+    pub seeding_time: Option<Duration>,
+    /// 添加时间
+    pub added_at: Option<SystemTime>,
+    /// 下载完成时间
+    pub completed_at: Option<SystemTime>,
+    /// tracker 或下载器返回的消息
+    pub message: Option<String>,
+    /// 下载项中的文件
+    pub files: Vec<DownloadFile>,
+    /// 获取文件列表失败时的错误，此时文件列表为空
+    pub files_error: Option<String>,
+}
+
+impl DownloadItem {
+    /// 只有基本信息的下载项，其余信息为空
+    fn new(downloader: u32, id: String, status: ItemStatus, path: PathBuf) -> Self {
+        Self {
+            downloader,
+            id,
+            status,
+            path,
+            progress: 0.0,
+            size: 0,
+            downloaded: 0,
+            uploaded: 0,
+            download_speed: 0,
+            upload_speed: 0,
+            ratio: 0.0,
+            eta: None,
+            seeding_time: None,
+            added_at: None,
+            completed_at: None,
+            message: None,
+            files: vec![],
+            files_error: None,
+        }
+    }
+}
+
+/// 下载项中的文件信息
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownloadFile {
+    /// 文件本地路径
+    pub path: PathBuf,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 下载进度，0 ~ 1
+    pub progress: f64,
+}
+
+/// unix 时间戳（秒）转换为时间，小于等于 0 表示没有时间
+fn timestamp(secs: i64) -> Option<SystemTime> {
+    let secs = u64::try_from(secs).ok().filter(|it| *it > 0)?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// 根据剩余大小和下载速度估计剩余时间
+fn eta(remaining: u64, speed: u64) -> Option<Duration> {
+    match (remaining, speed) {
+        (0, _) | (_, 0) => None,
+        _ => Some(Duration::from_secs(remaining.div_ceil(speed))),
+    }
+}
+
+/// 已完成大小占总大小的比例，总大小为 0 时为 0
+fn progress(completed: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => completed as f64 / total as f64,
+    }
+}
+
+/// 已上传大小和已下载大小计算分享率
+fn ratio(uploaded: u64, downloaded: u64) -> f64 {
+    progress(uploaded, downloaded)
+}
+
+/// 非空的消息
+fn message(message: String) -> Option<String> {
+    Some(message).filter(|it| !it.trim().is_empty())
 }
 
 pub enum Downloader {
     Aira2(aira2::Client),
     Qbittorrent(qbittorrent::Client),
     Transmission(transmission::Client),
+    Deluge(deluge::Client),
+    Rtorrent(rtorrent::Client),
 }
 
 impl Downloader {
-    pub async fn with(id: u32) -> Result<Model> {
+    /// 获取数据库中保存的下载器配置
+    pub async fn model(id: u32) -> Result<Model> {
         Entity::find_by_id(id)
             .one(database())
             .await?
@@ -57,15 +161,101 @@ impl Downloader {
             Downloader::Aira2(it) => it.connect_test().await,
             Downloader::Qbittorrent(it) => it.connect_test().await,
             Downloader::Transmission(it) => it.connect_test().await,
+            Downloader::Deluge(it) => it.connect_test().await,
+            Downloader::Rtorrent(it) => it.connect_test().await,
         }
     }
 
     /// 下载器添加 torrent
     pub async fn download(self, torrent: &[u8], info_hash: &str) -> Result<DownloadItem> {
-        match self {
+        let item = match self {
             Downloader::Aira2(it) => it.download(torrent).await,
             Downloader::Qbittorrent(it) => it.download(torrent, info_hash).await,
             Downloader::Transmission(it) => it.download(torrent).await,
+            Downloader::Deluge(it) => it.download(torrent, info_hash).await,
+            Downloader::Rtorrent(it) => it.download(torrent, info_hash).await,
+        }?;
+        Ok(added(item, info_hash).await)
+    }
+
+    /// 下载器直接添加 magnet 链接，由下载器获取 torrent 元数据，
+    /// 元数据获取完成前下载项路径为空
+    pub async fn download_magnet(self, magnet: &str) -> Result<DownloadItem> {
+        let info_hash = magnet_hash(magnet)?;
+        let item = match self {
+            Downloader::Aira2(it) => it.download_magnet(magnet).await,
+            Downloader::Qbittorrent(it) => it.download_magnet(magnet, &info_hash).await,
+            Downloader::Transmission(it) => it.download_magnet(magnet).await,
+            Downloader::Deluge(it) => it.download_magnet(magnet, &info_hash).await,
+            Downloader::Rtorrent(it) => it.download_magnet(magnet, &info_hash).await,
+        }?;
+        Ok(added(item, &info_hash).await)
+    }
+
+    /// 暂停下载项
+    pub async fn pause(self, id: &str) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.pause(id).await,
+            Downloader::Qbittorrent(it) => it.pause(id).await,
+            Downloader::Transmission(it) => it.pause(id).await,
+            Downloader::Deluge(it) => it.pause(id).await,
+            Downloader::Rtorrent(it) => it.pause(id).await,
+        }
+    }
+
+    /// 继续下载项
+    pub async fn resume(self, id: &str) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.resume(id).await,
+            Downloader::Qbittorrent(it) => it.resume(id).await,
+            Downloader::Transmission(it) => it.resume(id).await,
+            Downloader::Deluge(it) => it.resume(id).await,
+            Downloader::Rtorrent(it) => it.resume(id).await,
+        }
+    }
+
+    /// 删除下载项，delete_data 为 true 时同时删除已下载的文件
+    pub async fn remove(self, id: &str, delete_data: bool) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.remove(id, delete_data).await,
+            Downloader::Qbittorrent(it) => it.remove(id, delete_data).await,
+            Downloader::Transmission(it) => it.remove(id, delete_data).await,
+            Downloader::Deluge(it) => it.remove(id, delete_data).await,
+            Downloader::Rtorrent(it) => it.remove(id, delete_data).await,
+        }
+    }
+
+    /// 重新校验下载项
+    pub async fn recheck(self, id: &str) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.recheck(id).await,
+            Downloader::Qbittorrent(it) => it.recheck(id).await,
+            Downloader::Transmission(it) => it.recheck(id).await,
+            Downloader::Deluge(it) => it.recheck(id).await,
+            Downloader::Rtorrent(it) => it.recheck(id).await,
+        }
+    }
+
+    /// 设置下载项分类（qBittorrent 为 tag，Transmission 为 label），不影响下载项跟踪，
+    /// Deluge 和 rTorrent 只有一个标签用于跟踪下载项，不支持其他分类
+    pub async fn set_category(self, id: &str, category: &str) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.set_category(id, category).await,
+            Downloader::Qbittorrent(it) => it.set_category(id, category).await,
+            Downloader::Transmission(it) => it.set_category(id, category).await,
+            Downloader::Deluge(it) => it.set_category(id, category).await,
+            Downloader::Rtorrent(it) => it.set_category(id, category).await,
+        }
+    }
+
+    /// 设置下载项限速和做种分享率
+    pub async fn set_limits(self, id: &str, limits: &TorrentLimits) -> Result<()> {
+        match self {
+            Downloader::Aira2(it) => it.set_limits(id, limits).await,
+            Downloader::Qbittorrent(it) => it.set_limits(id, limits).await,
+            Downloader::Transmission(it) => it.set_limits(id, limits).await,
+            Downloader::Deluge(it) => it.set_limits(id, limits).await,
+            Downloader::Rtorrent(it) => it.set_limits(id, limits).await,
         }
     }
 
@@ -75,16 +265,55 @@ impl Downloader {
             Downloader::Aira2(it) => it.download_list().await,
             Downloader::Qbittorrent(it) => it.download_list().await,
             Downloader::Transmission(it) => it.download_list().await,
+            Downloader::Deluge(it) => it.download_list().await,
+            Downloader::Rtorrent(it) => it.download_list().await,
         }
     }
 }
 
+/// 跟踪新添加的下载项，之后轮询时可以发现下载完成，
+/// 下载项已添加到下载器，记录失败时不返回错误，避免重复添加，下次轮询时会重新记录
+async fn added(item: DownloadItem, info_hash: &str) -> DownloadItem {
+    watcher::track(&item).await.ok();
+    publish(TorrentAdded {
+        downloader: item.downloader,
+        id: item.id.clone(),
+        info_hash: info_hash.to_owned(),
+    });
+    item
+}
+
+/// 删除文件或文件夹，不存在时忽略，用于不支持删除数据的下载器
+fn remove_path(path: &Path) -> Result<()> {
+    let remove = match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    };
+    match remove {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 下载项限速和做种设置，None 表示不修改
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TorrentLimits {
+    /// 上传速度限制（字节/秒），0 表示不限制
+    pub upload: Option<u64>,
+    /// 下载速度限制（字节/秒），0 表示不限制
+    pub download: Option<u64>,
+    /// 做种分享率，达到后停止做种
+    pub ratio: Option<f64>,
+}
+
 impl From<Model> for Downloader {
     fn from(value: Model) -> Self {
         match value.cat {
             Category::Aira2 => Self::Aira2(value.into()),
             Category::Qbittorrent => Self::Qbittorrent(value.into()),
             Category::Transmission => Self::Transmission(value.into()),
+            Category::Deluge => Self::Deluge(value.into()),
+            Category::Rtorrent => Self::Rtorrent(value.into()),
         }
     }
 }
@@ -0,0 +1,46 @@
+use core::entity::download_client::Model;
+use std::path::{Path, PathBuf};
+
+/// 下载器路径到本地路径的转换，各下载器共用
+#[derive(Clone, Debug)]
+pub(crate) struct PathMapper {
+    /// 路径前缀映射，依次为下载器路径和本地路径，包含 download_dir 到 local_dir 的映射
+    mappings: Vec<(PathBuf, PathBuf)>,
+    local_dir: PathBuf,
+}
+
+impl PathMapper {
+    /// 使用最长匹配的路径前缀转换为本地路径，
+    /// 都不匹配时使用 local_dir 拼接下载器路径的最后一部分
+    pub(crate) fn local(&self, remote: impl AsRef<Path>) -> Option<PathBuf> {
+        let remote = remote.as_ref();
+        let mapped = self.mappings.iter().filter_map(|(prefix, local)| {
+            let relative = remote.strip_prefix(prefix).ok()?;
+            Some((prefix.components().count(), local, relative))
+        });
+        // 相同长度时使用先配置的映射
+        let mapped = mapped.rev().max_by_key(|it| it.0);
+        if let Some((_, local, relative)) = mapped {
+            return Some(local.join(relative));
+        }
+        let name = remote.components().next_back()?;
+        Some(self.local_dir.join(name))
+    }
+}
+
+impl From<&Model> for PathMapper {
+    fn from(value: &Model) -> Self {
+        let mappings = value.path_mappings.0.iter();
+        let mut mappings: Vec<_> = mappings
+            .map(|it| (PathBuf::from(it.remote.trim()), PathBuf::from(&it.local)))
+            .collect();
+        if !value.download_dir.is_empty() {
+            let download_dir = PathBuf::from(&value.download_dir);
+            mappings.push((download_dir, PathBuf::from(&value.local_dir)));
+        }
+        Self {
+            mappings,
+            local_dir: PathBuf::from(&value.local_dir),
+        }
+    }
+}
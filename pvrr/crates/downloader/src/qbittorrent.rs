@@ -1,11 +1,18 @@
-use crate::{DownloadItem, ItemStatus, DEFAULT_CATEGORY};
+use crate::mapping::PathMapper;
+use crate::{timestamp, DownloadFile, DownloadItem, ItemStatus, TorrentLimits};
+use crate::{DEFAULT_CATEGORY, FILES_CONCURRENCY};
 use anyhow::{Context, Result};
 use core::entity::download_client::Model;
 use core::request::{direct, Method, Req, Resp, StatusCode};
+use futures::{stream, StreamExt};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// 无法估计剩余时间时返回的 eta
+const MAX_ETA: i64 = 8640000;
 
 /// 登录参数
 #[derive(Serialize)]
@@ -22,24 +29,100 @@ struct TorrentAddArgs<'a> {
     category: &'a str,
 }
 
+/// 下载链接参数
+#[derive(Serialize)]
+struct UrlAddArgs<'a> {
+    urls: &'a str,
+    savepath: &'a str,
+    category: &'a str,
+}
+
 /// 获取 torrent 列表参数
 #[derive(Serialize)]
 struct TorrentListArgs<'a> {
     category: &'a str,
 }
 
-/// torrent 信息
-#[derive(Deserialize)]
+/// 指定 torrent 参数
+#[derive(Serialize)]
+struct HashesArgs<'a> {
+    hashes: &'a str,
+}
+
+/// 删除 torrent 参数
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteArgs<'a> {
+    hashes: &'a str,
+    delete_files: bool,
+}
+
+/// 添加标签参数，多个标签使用逗号分隔
+#[derive(Serialize)]
+struct TagsArgs<'a> {
+    hashes: &'a str,
+    tags: &'a str,
+}
+
+/// 限速参数，limit 为字节/秒，0 表示不限制
+#[derive(Serialize)]
+struct LimitArgs<'a> {
+    hashes: &'a str,
+    limit: u64,
+}
+
+/// 做种限制参数，-2 表示使用全局设置，-1 表示不限制
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShareLimitsArgs<'a> {
+    hashes: &'a str,
+    ratio_limit: f64,
+    seeding_time_limit: i64,
+    inactive_seeding_time_limit: i64,
+}
+
+/// 获取 torrent 文件参数
+#[derive(Serialize)]
+struct HashArgs<'a> {
+    hash: &'a str,
+}
+
+/// torrent 信息，速度单位为字节/秒，时间单位为秒
+#[derive(Default, Deserialize)]
+#[serde(default)]
 struct TorrentInfo {
     hash: String,
     state: String,
     content_path: String,
+    save_path: String,
+    /// 下载进度，0 ~ 1
+    progress: f64,
+    /// 选择下载的文件大小
+    size: u64,
+    downloaded: u64,
+    uploaded: u64,
+    dlspeed: u64,
+    upspeed: u64,
+    ratio: f64,
+    /// 8640000 表示无法估计
+    eta: i64,
+    seeding_time: i64,
+    added_on: i64,
+    /// 未完成时为 0 或 -1
+    completion_on: i64,
+}
+
+/// torrent 文件信息，name 为相对于 save_path 的路径
+#[derive(Deserialize)]
+struct TorrentFile {
+    name: String,
+    size: u64,
+    progress: f64,
 }
 
 impl TorrentInfo {
-    fn into_item(self, id: u32, local: &str) -> DownloadItem {
-        let name = Path::new(&self.content_path).components().last();
-        let path = name.map(|it| Path::new(local).join(it));
+    fn into_item(self, id: u32, paths: &PathMapper) -> DownloadItem {
+        let path = paths.local(&self.content_path);
         let downloader = id;
         let id = self.hash;
         let status = match self.state.as_str() {
@@ -51,12 +134,22 @@ impl TorrentInfo {
             }
             _ => ItemStatus::Error,
         };
-        DownloadItem {
-            downloader,
-            id,
-            status,
-            path: path.unwrap_or_default(),
-        }
+
+        let mut item = DownloadItem::new(downloader, id, status, path.unwrap_or_default());
+        item.progress = self.progress;
+        item.size = self.size;
+        item.downloaded = self.downloaded;
+        item.uploaded = self.uploaded;
+        item.download_speed = self.dlspeed;
+        item.upload_speed = self.upspeed;
+        item.ratio = self.ratio;
+        let eta = Some(self.eta).filter(|it| (1..MAX_ETA).contains(it));
+        item.eta = eta.map(|it| Duration::from_secs(it as u64));
+        let seeding_time = Some(self.seeding_time).filter(|it| *it > 0);
+        item.seeding_time = seeding_time.map(|it| Duration::from_secs(it as u64));
+        item.added_at = timestamp(self.added_on);
+        item.completed_at = timestamp(self.completion_on);
+        item
     }
 }
 
@@ -66,7 +159,7 @@ pub struct Client {
     username: Option<String>,
     password: Option<String>,
     download_dir: String,
-    local_dir: String,
+    paths: PathMapper,
     category: Arc<str>,
 }
 
@@ -78,22 +171,131 @@ impl Client {
     pub(crate) async fn download(&self, torrent: &[u8], hash: &str) -> Result<DownloadItem> {
         self.add_torrent(torrent).await?;
         let list = self.torrent_info().await?;
-        list.into_iter()
-            .find(|it| it.hash == hash)
-            .map(|it| it.into_item(self.id, &self.local_dir))
-            .context("Can't find torrent.")
+        let info = list.into_iter().find(|it| it.hash == hash);
+        Ok(self.item(info.context("Can't find torrent.")?).await)
+    }
+
+    /// 获取元数据前 qBittorrent 可能还未列出下载项，此时返回下载中的空路径下载项
+    pub(crate) async fn download_magnet(&self, magnet: &str, hash: &str) -> Result<DownloadItem> {
+        let args = UrlAddArgs {
+            urls: magnet,
+            savepath: &self.download_dir,
+            category: self.category.as_ref(),
+        };
+        self.post("/api/v2/torrents/add", &args).await?;
+        let list = self.torrent_info().await?;
+        match list.into_iter().find(|it| it.hash == hash) {
+            Some(info) => Ok(self.item(info).await),
+            None => {
+                let id = hash.to_owned();
+                let status = ItemStatus::Downloading;
+                Ok(DownloadItem::new(self.id, id, status, Default::default()))
+            }
+        }
     }
 
     pub(crate) async fn download_list(&self) -> Result<Vec<DownloadItem>> {
         let list = self.torrent_info().await?;
-        let list = list
-            .into_iter()
-            .map(|it| it.into_item(self.id, &self.local_dir));
-        Ok(list.collect())
+        let list = stream::iter(list).map(|it| self.item(it));
+        Ok(list.buffered(FILES_CONCURRENCY).collect().await)
+    }
+
+    pub(crate) async fn pause(&self, hash: &str) -> Result<()> {
+        self.post("/api/v2/torrents/pause", &HashesArgs { hashes: hash })
+            .await
+    }
+
+    pub(crate) async fn resume(&self, hash: &str) -> Result<()> {
+        self.post("/api/v2/torrents/resume", &HashesArgs { hashes: hash })
+            .await
+    }
+
+    pub(crate) async fn remove(&self, hash: &str, delete_files: bool) -> Result<()> {
+        let args = DeleteArgs {
+            hashes: hash,
+            delete_files,
+        };
+        self.post("/api/v2/torrents/delete", &args).await
+    }
+
+    pub(crate) async fn recheck(&self, hash: &str) -> Result<()> {
+        self.post("/api/v2/torrents/recheck", &HashesArgs { hashes: hash })
+            .await
+    }
+
+    /// 分类用于跟踪下载项，因此使用标签，只添加标签，不影响已有的其他标签
+    pub(crate) async fn set_category(&self, hash: &str, category: &str) -> Result<()> {
+        let args = TagsArgs {
+            hashes: hash,
+            tags: category,
+        };
+        self.post("/api/v2/torrents/addTags", &args).await
+    }
+
+    pub(crate) async fn set_limits(&self, hash: &str, limits: &TorrentLimits) -> Result<()> {
+        if let Some(limit) = limits.upload {
+            let args = LimitArgs {
+                hashes: hash,
+                limit,
+            };
+            self.post("/api/v2/torrents/setUploadLimit", &args).await?;
+        }
+        if let Some(limit) = limits.download {
+            let args = LimitArgs {
+                hashes: hash,
+                limit,
+            };
+            self.post("/api/v2/torrents/setDownloadLimit", &args)
+                .await?;
+        }
+        if let Some(ratio_limit) = limits.ratio {
+            let args = ShareLimitsArgs {
+                hashes: hash,
+                ratio_limit,
+                seeding_time_limit: -2,
+                inactive_seeding_time_limit: -2,
+            };
+            self.post("/api/v2/torrents/setShareLimits", &args).await?;
+        }
+        Ok(())
     }
 }
 
 impl Client {
+    /// 下载项信息，文件列表需要单独获取，获取失败时记录错误，文件列表为空
+    async fn item(&self, info: TorrentInfo) -> DownloadItem {
+        // 获取元数据前没有文件
+        let files = match info.state.as_str() {
+            "metaDL" => Ok(vec![]),
+            _ => self.torrent_files(&info.hash).await,
+        };
+        let save_path = Path::new(&info.save_path).to_owned();
+        let mut item = info.into_item(self.id, &self.paths);
+        let files = match files {
+            Ok(it) => it,
+            Err(e) => {
+                item.files_error = Some(format!("{e:#}"));
+                return item;
+            }
+        };
+        let files = files.into_iter().filter_map(|it| {
+            Some(DownloadFile {
+                path: self.paths.local(save_path.join(&it.name))?,
+                size: it.size,
+                progress: it.progress,
+            })
+        });
+        item.files = files.collect();
+        item
+    }
+
+    async fn torrent_files(&self, hash: &str) -> Result<Vec<TorrentFile>> {
+        let req = self.build_req("/api/v2/torrents/files", Method::GET, |it| {
+            it.query(&HashArgs { hash })
+        });
+        self.api(req).await
+    }
+
     async fn app_version(&self) -> Result<()> {
         let req = self.build_req("/api/v2/app/version", Method::GET, |it| it);
         self.api_without_resp(req).await
@@ -111,6 +313,12 @@ impl Client {
         Ok(())
     }
 
+    /// 发送表单参数的 POST 请求
+    async fn post<T: Serialize + ?Sized>(&self, path: &str, form: &T) -> Result<()> {
+        let req = self.build_req(path, Method::POST, |it| it.form(form));
+        self.api_without_resp(req).await
+    }
+
     async fn torrent_info(&self) -> Result<Vec<TorrentInfo>> {
         let req = self.build_req("/api/v2/torrents/info", Method::GET, |it| {
             it.query(&TorrentListArgs {
@@ -126,9 +334,9 @@ impl Client {
     /// 尝试登录
     async fn login(&self) -> Result<()> {
         #[rustfmt::skip]
-        let username = self.username.as_ref().map(String::as_str).unwrap_or_default();
+        let username = self.username.as_deref().unwrap_or_default();
         #[rustfmt::skip]
-        let password = self.password.as_ref().map(String::as_str).unwrap_or_default();
+        let password = self.password.as_deref().unwrap_or_default();
         let req = self.build_req("/api/v2/auth/login", Method::POST, |it| {
             it.form(&LoginArgs { username, password })
         });
@@ -140,7 +348,7 @@ impl Client {
 impl Client {
     fn build_req<T: FnOnce(Req) -> Req>(&self, path: &str, method: Method, param: T) -> Req {
         let url = format!("{}{path}", self.url);
-        let req = direct().request(method, &url);
+        let req = direct().without_rate_limit().request(method, &url);
         param(req)
     }
 
@@ -173,13 +381,14 @@ impl Client {
 
 impl From<Model> for Client {
     fn from(value: Model) -> Self {
+        let paths = PathMapper::from(&value);
         Self {
             id: value.id,
             url: value.url,
             username: value.username,
             password: value.password,
             download_dir: value.download_dir,
-            local_dir: value.local_dir,
+            paths,
             category: value
                 .category
                 .map(|it| it.into())
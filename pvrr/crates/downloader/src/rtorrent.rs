@@ -0,0 +1,573 @@
+use crate::mapping::PathMapper;
+use crate::{
+    eta, message, progress, remove_path, timestamp, DownloadFile, DownloadItem, ItemStatus,
+    TorrentLimits, DEFAULT_CATEGORY, FILES_CONCURRENCY,
+};
+use anyhow::{bail, ensure, Context, Result};
+use base64::Engine;
+use core::entity::download_client::Model;
+use core::request::direct;
+use futures::{stream, StreamExt};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// SCGI 请求超时时间
+const SCGI_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 下载项列表字段，顺序与 [TorrentInfo::new] 解析顺序一致
+static TORRENT_FIELDS: [&str; 16] = [
+    "d.hash=",
+    "d.name=",
+    "d.custom1=",
+    "d.complete=",
+    "d.is_active=",
+    "d.message=",
+    "d.directory=",
+    "d.is_multi_file=",
+    "d.size_bytes=",
+    "d.completed_bytes=",
+    "d.up.total=",
+    "d.down.rate=",
+    "d.up.rate=",
+    "d.ratio=",
+    "d.load_date=",
+    "d.timestamp.finished=",
+];
+
+/// 文件列表字段
+static FILE_FIELDS: [&str; 4] = [
+    "f.path=",
+    "f.size_bytes=",
+    "f.completed_chunks=",
+    "f.size_chunks=",
+];
+
+/// XML-RPC 参数和返回值
+#[derive(Clone, Debug, PartialEq)]
+enum XmlValue {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+    Double(f64),
+    Base64(String),
+    Array(Vec<XmlValue>),
+    Struct(Vec<(String, XmlValue)>),
+    Nil,
+}
+
+impl XmlValue {
+    fn as_str(&self) -> &str {
+        match self {
+            XmlValue::Str(it) => it.as_str(),
+            _ => "",
+        }
+    }
+
+    fn as_u64(&self) -> u64 {
+        u64::try_from(self.as_i64()).unwrap_or_default()
+    }
+
+    fn as_i64(&self) -> i64 {
+        match self {
+            XmlValue::Int(it) => *it,
+            XmlValue::Bool(it) => *it as i64,
+            _ => 0,
+        }
+    }
+
+    fn write(&self, xml: &mut String) {
+        xml.push_str("<value>");
+        match self {
+            XmlValue::Int(it) => xml.push_str(&format!("<i8>{it}</i8>")),
+            XmlValue::Bool(it) => xml.push_str(&format!("<boolean>{}</boolean>", *it as u8)),
+            XmlValue::Str(it) => xml.push_str(&format!("<string>{}</string>", escape(it))),
+            XmlValue::Double(it) => xml.push_str(&format!("<double>{it}</double>")),
+            XmlValue::Base64(it) => xml.push_str(&format!("<base64>{it}</base64>")),
+            XmlValue::Array(list) => {
+                xml.push_str("<array><data>");
+                list.iter().for_each(|it| it.write(xml));
+                xml.push_str("</data></array>");
+            }
+            XmlValue::Struct(members) => {
+                xml.push_str("<struct>");
+                for (name, value) in members {
+                    xml.push_str(&format!("<member><name>{}</name>", escape(name)));
+                    value.write(xml);
+                    xml.push_str("</member>");
+                }
+                xml.push_str("</struct>");
+            }
+            XmlValue::Nil => xml.push_str("<nil/>"),
+        }
+        xml.push_str("</value>");
+    }
+}
+
+impl From<&str> for XmlValue {
+    fn from(value: &str) -> Self {
+        XmlValue::Str(value.to_owned())
+    }
+}
+
+fn escape(text: &str) -> String {
+    quick_xml::escape::escape(text).into_owned()
+}
+
+/// rTorrent 命令中的字符串参数，使用双引号包裹并转义引号和反斜杠
+fn command_arg(value: &str) -> String {
+    let value = value.replace('\\', r"\\").replace('"', r#"\""#);
+    format!(r#""{value}""#)
+}
+
+/// 构建 XML-RPC 请求
+fn method_call(method: &str, params: &[XmlValue]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0"?><methodCall>"#);
+    xml.push_str(&format!(
+        "<methodName>{}</methodName><params>",
+        escape(method)
+    ));
+    for param in params {
+        xml.push_str("<param>");
+        param.write(&mut xml);
+        xml.push_str("</param>");
+    }
+    xml.push_str("</params></methodCall>");
+    xml
+}
+
+/// 解析 XML-RPC 响应，fault 响应转换为错误
+fn method_response(xml: &str) -> Result<XmlValue> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut fault = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"fault" => fault = true,
+            Event::Start(e) if e.name().as_ref() == b"value" => break,
+            Event::Eof => bail!("rTorrent response without value"),
+            _ => {}
+        }
+    }
+    let value = read_value(&mut reader)?;
+    if !fault {
+        return Ok(value);
+    }
+
+    let member = |name: &str| match &value {
+        XmlValue::Struct(members) => members.iter().find(|it| it.0 == name).map(|it| &it.1),
+        _ => None,
+    };
+    let code = member("faultCode")
+        .map(XmlValue::as_i64)
+        .unwrap_or_default();
+    let message = member("faultString")
+        .map(XmlValue::as_str)
+        .unwrap_or_default();
+    bail!("rTorrent fault {code}: {message}")
+}
+
+/// 读取 value 标签内容，调用时 value 开始标签已读取
+fn read_value(reader: &mut Reader<&[u8]>) -> Result<XmlValue> {
+    let value = match reader.read_event()? {
+        // 没有类型标签时为字符串
+        Event::Text(text) => {
+            let value = XmlValue::Str(text.unescape()?.into_owned());
+            reader.read_event()?;
+            return Ok(value);
+        }
+        Event::End(_) => return Ok(XmlValue::Str(String::new())),
+        Event::Empty(e) => match e.name().as_ref() {
+            b"nil" => XmlValue::Nil,
+            b"array" => XmlValue::Array(vec![]),
+            b"struct" => XmlValue::Struct(vec![]),
+            _ => XmlValue::Str(String::new()),
+        },
+        Event::Start(e) => {
+            let tag = e.name().as_ref().to_vec();
+            match tag.as_slice() {
+                b"array" => XmlValue::Array(read_array(reader)?),
+                b"struct" => XmlValue::Struct(read_struct(reader)?),
+                tag => {
+                    let text = read_text(reader)?;
+                    match tag {
+                        b"i4" | b"i8" | b"int" => XmlValue::Int(text.trim().parse()?),
+                        b"boolean" => XmlValue::Bool(text.trim() == "1"),
+                        b"double" => XmlValue::Double(text.trim().parse()?),
+                        b"base64" => XmlValue::Base64(text),
+                        _ => XmlValue::Str(text),
+                    }
+                }
+            }
+        }
+        event => bail!("Unexpected rTorrent response {event:?}"),
+    };
+    // value 结束标签
+    reader.read_event()?;
+    Ok(value)
+}
+
+/// 读取标签文本直到结束标签
+fn read_text(reader: &mut Reader<&[u8]>) -> Result<String> {
+    let mut text = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Text(it) => text.push_str(&it.unescape()?),
+            Event::CData(it) => text.push_str(&String::from_utf8_lossy(&it)),
+            Event::End(_) => return Ok(text),
+            Event::Eof => bail!("Unexpected rTorrent response eof"),
+            _ => {}
+        }
+    }
+}
+
+fn read_array(reader: &mut Reader<&[u8]>) -> Result<Vec<XmlValue>> {
+    let mut list = vec![];
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"value" => list.push(read_value(reader)?),
+            Event::End(e) if e.name().as_ref() == b"array" => return Ok(list),
+            Event::Eof => bail!("Unexpected rTorrent response eof"),
+            _ => {}
+        }
+    }
+}
+
+fn read_struct(reader: &mut Reader<&[u8]>) -> Result<Vec<(String, XmlValue)>> {
+    let mut members = vec![];
+    let mut name = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"name" => name = read_text(reader)?,
+            Event::Start(e) if e.name().as_ref() == b"value" => {
+                members.push((std::mem::take(&mut name), read_value(reader)?));
+            }
+            Event::End(e) if e.name().as_ref() == b"struct" => return Ok(members),
+            Event::Eof => bail!("Unexpected rTorrent response eof"),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TorrentInfo {
+    hash: String,
+    name: String,
+    label: String,
+    complete: bool,
+    is_active: bool,
+    message: String,
+    /// 多文件时为下载项目录，单文件时为文件所在目录
+    directory: String,
+    is_multi_file: bool,
+    size: u64,
+    completed: u64,
+    uploaded: u64,
+    download_speed: u64,
+    upload_speed: u64,
+    /// 分享率的千分之一
+    ratio: i64,
+    load_date: i64,
+    finished: i64,
+}
+
+impl TorrentInfo {
+    fn new(value: &XmlValue) -> Option<Self> {
+        let fields = match value {
+            XmlValue::Array(it) if it.len() >= TORRENT_FIELDS.len() => it,
+            _ => return None,
+        };
+        Some(Self {
+            hash: fields[0].as_str().to_lowercase(),
+            name: fields[1].as_str().to_owned(),
+            label: fields[2].as_str().to_owned(),
+            complete: fields[3].as_i64() == 1,
+            is_active: fields[4].as_i64() == 1,
+            message: fields[5].as_str().to_owned(),
+            directory: fields[6].as_str().to_owned(),
+            is_multi_file: fields[7].as_i64() == 1,
+            size: fields[8].as_u64(),
+            completed: fields[9].as_u64(),
+            uploaded: fields[10].as_u64(),
+            download_speed: fields[11].as_u64(),
+            upload_speed: fields[12].as_u64(),
+            ratio: fields[13].as_i64(),
+            load_date: fields[14].as_i64(),
+            finished: fields[15].as_i64(),
+        })
+    }
+
+    /// 下载器中的下载项路径
+    fn remote(&self) -> PathBuf {
+        match self.is_multi_file {
+            true => PathBuf::from(&self.directory),
+            false => Path::new(&self.directory).join(&self.name),
+        }
+    }
+
+    fn into_item(self, id: u32, paths: &PathMapper) -> DownloadItem {
+        let path = paths.local(self.remote());
+        let downloader = id;
+        let status = match (self.complete, self.is_active) {
+            _ if path.is_none() => ItemStatus::Error,
+            // 停止且有错误信息时为下载错误，运行中的错误信息多为 tracker 警告
+            (false, false) if !self.message.is_empty() => ItemStatus::Error,
+            (false, _) => ItemStatus::Downloading,
+            (true, true) => ItemStatus::Downloaded,
+            // 做种完成后停止
+            (true, false) => ItemStatus::Complete,
+        };
+
+        let path = path.unwrap_or_default();
+        let mut item = DownloadItem::new(downloader, self.hash, status, path);
+        item.progress = progress(self.completed, self.size);
+        item.size = self.size;
+        item.downloaded = self.completed;
+        item.uploaded = self.uploaded;
+        item.download_speed = self.download_speed;
+        item.upload_speed = self.upload_speed;
+        item.ratio = self.ratio as f64 / 1000.0;
+        let remaining = self.size - self.completed.min(self.size);
+        item.eta = eta(remaining, self.download_speed);
+        item.added_at = timestamp(self.load_date);
+        item.completed_at = timestamp(self.finished);
+        // 下载完成并且仍在运行时为做种中
+        let seeding = item
+            .completed_at
+            .filter(|_| self.complete && self.is_active);
+        item.seeding_time = seeding.and_then(|it| it.elapsed().ok());
+        item.message = message(self.message);
+        item
+    }
+}
+
+/// url 为 XML-RPC 的 http 地址，例如 http://host/RPC2，或 SCGI 地址，例如 scgi://host:5000
+pub struct Client {
+    id: u32,
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    download_dir: String,
+    paths: PathMapper,
+    category: Arc<str>,
+}
+
+impl Client {
+    pub(crate) async fn connect_test(&self) -> Result<()> {
+        self.rpc("system.client_version", vec![]).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn download(&self, torrent: &[u8], hash: &str) -> Result<DownloadItem> {
+        let torrent = base64::engine::general_purpose::STANDARD.encode(torrent);
+        self.load("load.raw_start", XmlValue::Base64(torrent))
+            .await?;
+        self.added(hash).await
+    }
+
+    pub(crate) async fn download_magnet(&self, magnet: &str, hash: &str) -> Result<DownloadItem> {
+        self.load("load.start", magnet.into()).await?;
+        self.added(hash).await
+    }
+
+    pub(crate) async fn download_list(&self) -> Result<Vec<DownloadItem>> {
+        let list = self.torrent_list().await?.into_iter();
+        let list = list.filter(|it| it.label == self.category.as_ref());
+        let list = stream::iter(list).map(|it| self.item(it));
+        Ok(list.buffered(FILES_CONCURRENCY).collect().await)
+    }
+
+    pub(crate) async fn pause(&self, hash: &str) -> Result<()> {
+        self.hash_rpc("d.stop", hash).await
+    }
+
+    pub(crate) async fn resume(&self, hash: &str) -> Result<()> {
+        self.hash_rpc("d.start", hash).await
+    }
+
+    /// rTorrent 删除下载项时不会删除文件，需要删除时直接删除本地文件
+    pub(crate) async fn remove(&self, hash: &str, delete_data: bool) -> Result<()> {
+        let hash = hash.to_lowercase();
+        let list = self.torrent_list().await?;
+        let info = list.into_iter().find(|it| it.hash == hash);
+        let info = info.context("Can't find torrent.")?;
+        self.hash_rpc("d.erase", &hash).await?;
+        let path = self.paths.local(info.remote());
+        if let Some(path) = path.filter(|_| delete_data) {
+            remove_path(&path)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn recheck(&self, hash: &str) -> Result<()> {
+        self.hash_rpc("d.check_hash", hash).await
+    }
+
+    /// 使用 custom1 作为标签，与 ruTorrent 一致，
+    /// 标签用于跟踪下载项，因此不能设置其他分类
+    pub(crate) async fn set_category(&self, hash: &str, category: &str) -> Result<()> {
+        ensure!(
+            category == self.category.as_ref(),
+            "rTorrent label is used to track downloads, can't set category `{category}`"
+        );
+        let params = vec![hash.to_uppercase().as_str().into(), category.into()];
+        self.rpc("d.custom1.set", params).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn set_limits(&self, _hash: &str, _limits: &TorrentLimits) -> Result<()> {
+        bail!("rTorrent unsupported per torrent limits")
+    }
+}
+
+impl Client {
+    /// 添加并开始下载，同时设置下载路径和标签
+    async fn load(&self, method: &'static str, torrent: XmlValue) -> Result<()> {
+        let dir = format!("d.directory.set={}", command_arg(&self.download_dir));
+        let label = format!("d.custom1.set={}", command_arg(&self.category));
+        let params = vec![
+            "".into(),
+            torrent,
+            dir.as_str().into(),
+            label.as_str().into(),
+        ];
+        self.rpc(method, params).await?;
+        Ok(())
+    }
+
+    /// rTorrent 异步加载 torrent，magnet 获取元数据前可能还未列出下载项，
+    /// 此时返回下载中的空路径下载项
+    async fn added(&self, hash: &str) -> Result<DownloadItem> {
+        let list = self.torrent_list().await?;
+        match list.into_iter().find(|it| it.hash == hash) {
+            Some(info) => Ok(self.item(info).await),
+            None => {
+                let id = hash.to_owned();
+                let status = ItemStatus::Downloading;
+                Ok(DownloadItem::new(self.id, id, status, Default::default()))
+            }
+        }
+    }
+
+    /// 下载项信息，文件列表需要单独获取，获取失败时记录错误，文件列表为空
+    async fn item(&self, info: TorrentInfo) -> DownloadItem {
+        let mut params = vec![info.hash.to_uppercase().as_str().into(), "".into()];
+        params.extend(FILE_FIELDS.iter().map(|it| XmlValue::from(*it)));
+        let files = match self.rpc("f.multicall", params).await {
+            Ok(XmlValue::Array(it)) => Ok(it),
+            Ok(_) => Err("Invalid f.multicall response".to_owned()),
+            Err(e) => Err(format!("{e:#}")),
+        };
+        // 文件路径为相对于 d.directory 的路径
+        let dir = PathBuf::from(&info.directory);
+        let mut item = info.into_item(self.id, &self.paths);
+        let files = match files {
+            Ok(it) => it,
+            Err(e) => {
+                item.files_error = Some(e);
+                return item;
+            }
+        };
+        let files = files.iter().filter_map(|it| {
+            let fields = match it {
+                XmlValue::Array(it) if it.len() >= FILE_FIELDS.len() => it,
+                _ => return None,
+            };
+            Some(DownloadFile {
+                path: self.paths.local(dir.join(fields[0].as_str()))?,
+                size: fields[1].as_u64(),
+                progress: progress(fields[2].as_u64(), fields[3].as_u64()),
+            })
+        });
+        item.files = files.collect();
+        item
+    }
+
+    async fn torrent_list(&self) -> Result<Vec<TorrentInfo>> {
+        let mut params = vec!["".into(), "main".into()];
+        params.extend(TORRENT_FIELDS.iter().map(|it| XmlValue::from(*it)));
+        let list = match self.rpc("d.multicall2", params).await? {
+            XmlValue::Array(it) => it,
+            _ => bail!("rTorrent d.multicall2 response is not array"),
+        };
+        let list = list.iter().filter_map(TorrentInfo::new);
+        Ok(list.collect())
+    }
+
+    /// 只需要 hash 参数的方法，rTorrent 中 hash 为大写
+    async fn hash_rpc(&self, method: &'static str, hash: &str) -> Result<()> {
+        self.rpc(method, vec![hash.to_uppercase().as_str().into()])
+            .await?;
+        Ok(())
+    }
+
+    async fn rpc(&self, method: &'static str, params: Vec<XmlValue>) -> Result<XmlValue> {
+        let body = method_call(method, &params);
+        let text = match self.url.strip_prefix("scgi://") {
+            Some(addr) => scgi(addr.trim_end_matches('/'), &body).await,
+            None => self.http(body).await,
+        };
+        method_response(&text?).with_context(|| format!("rTorrent {method} error"))
+    }
+
+    async fn http(&self, body: String) -> Result<String> {
+        let mut req = direct().without_rate_limit().post(&self.url);
+        let username = self.username.as_ref();
+        if let Some(username) = username.filter(|it| !it.is_empty()) {
+            let password = self.password.as_ref();
+            req = req.basic_auth(username, password.filter(|it| !it.is_empty()));
+        }
+        let req = req.header("Content-Type", "text/xml");
+        let req = req.body(body);
+        let resp = req.send().await?.error_for_status()?;
+        resp.text().await
+    }
+}
+
+/// 通过 SCGI 发送请求，addr 为 host:port，不支持用户名和密码
+async fn scgi(addr: &str, body: &str) -> Result<String> {
+    let send = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        // 请求头为 netstring，CONTENT_LENGTH 必须是第一个
+        let headers = format!("CONTENT_LENGTH\0{}\0SCGI\01\0", body.len());
+        let req = format!("{}:{headers},{body}", headers.len());
+        stream.write_all(req.as_bytes()).await?;
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await?;
+        anyhow::Ok(resp)
+    };
+    let resp = tokio::time::timeout(SCGI_TIMEOUT, send).await;
+    let resp = resp.context("rTorrent SCGI request timed out")??;
+
+    // 响应为 CGI 格式，没有 Status 头时为 200
+    let (head, body) = resp
+        .split_once("\r\n\r\n")
+        .context("Invalid rTorrent SCGI response")?;
+    let status = head.lines().find_map(|it| it.strip_prefix("Status:"));
+    if let Some(status) = status.map(str::trim).filter(|it| !it.starts_with("200")) {
+        bail!("rTorrent SCGI request failed with status {status}");
+    }
+    Ok(body.to_owned())
+}
+
+impl From<Model> for Client {
+    fn from(value: Model) -> Self {
+        let paths = PathMapper::from(&value);
+        Self {
+            id: value.id,
+            url: value.url,
+            username: value.username,
+            password: value.password,
+            download_dir: value.download_dir,
+            paths,
+            category: value
+                .category
+                .map(|it| it.into())
+                .unwrap_or(DEFAULT_CATEGORY.clone()),
+        }
+    }
+}
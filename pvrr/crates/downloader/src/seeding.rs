@@ -0,0 +1,184 @@
+use crate::{watcher, DownloadItem, Downloader};
+use anyhow::{Context, Result};
+use core::database::{database, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use core::entity::download_client;
+use core::entity::download_item::{ActiveModel, Column, Entity, ItemStatus, Model};
+use core::event::{publish, SeedingTargetMet};
+use core::seeding::{SeedAction, SeedSource, SeedTarget, SeedingRule};
+use core::setting::SEEDING_RULE;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 单个下载项的做种检查结果
+#[derive(Clone, Debug)]
+pub struct SeedDecision {
+    /// 下载器 id
+    pub downloader: u32,
+    /// 下载项 id
+    pub id: String,
+    /// 下载项来源的索引器 id
+    pub indexer: Option<u32>,
+    pub ratio: f64,
+    pub seeding_time: Option<Duration>,
+    pub target: SeedTarget,
+    /// 达到做种目标时执行的操作，dry run 时为将要执行的操作
+    pub action: Option<SeedAction>,
+    /// 执行操作失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 做种检查报告
+#[derive(Clone, Debug, Default)]
+pub struct SeedReport {
+    pub dry_run: bool,
+    /// 有做种目标的下载项检查结果
+    pub decisions: Vec<SeedDecision>,
+    /// 获取下载项列表失败的下载器 id 和错误信息
+    pub errors: Vec<(u32, String)>,
+}
+
+/// 记录下载项来源的索引器和做种要求，下载项未跟踪时会先开始跟踪
+pub async fn link_source(item: &DownloadItem, source: &SeedSource) -> Result<()> {
+    watcher::track(item).await?;
+    let model = Entity::find()
+        .filter(Column::Downloader.eq(item.downloader))
+        .filter(Column::ItemId.eq(item.id.as_str()))
+        .one(database())
+        .await?;
+    let model = model.context("Can't find tracked download item")?;
+    let mut model: ActiveModel = model.into();
+    model.indexer = Set(Some(source.indexer));
+    model.minimum_ratio = Set(source.minimum_ratio);
+    model.minimum_seed_time = Set(source.minimum_seed_time);
+    model.update(database()).await?;
+    Ok(())
+}
+
+/// 使用保存的做种规则检查全部下载项
+pub async fn check_seeding() -> Result<SeedReport> {
+    check_seeding_with(&SEEDING_RULE.get().await?).await
+}
+
+/// 检查已下载完成且未处理的下载项，达到做种目标后执行规则中的操作，
+/// dry run 时只生成报告，单个下载器失败不影响其他下载器
+pub async fn check_seeding_with(rule: &SeedingRule) -> Result<SeedReport> {
+    check_listed(rule, None).await
+}
+
+/// 使用已获取的各下载器下载项列表检查做种目标，为 None 时获取各下载器的列表
+pub(crate) async fn check_listed(
+    rule: &SeedingRule,
+    mut listed: Option<HashMap<u32, Result<Vec<DownloadItem>>>>,
+) -> Result<SeedReport> {
+    let finished = [ItemStatus::Downloaded, ItemStatus::Complete];
+    let tracked = Entity::find()
+        .filter(Column::Seeded.eq(false))
+        .filter(Column::Status.is_in(finished))
+        .all(database())
+        .await?;
+    let mut grouped = HashMap::<_, Vec<_>>::new();
+    for model in tracked {
+        grouped.entry(model.downloader).or_default().push(model);
+    }
+
+    let mut report = SeedReport {
+        dry_run: rule.dry_run,
+        ..Default::default()
+    };
+    for (downloader, tracked) in grouped {
+        let client = download_client::Entity::find_by_id(downloader);
+        let Some(client) = client.one(database()).await? else {
+            continue;
+        };
+        let items = match listed.as_mut() {
+            // 轮询后添加的下载器没有列表，下次检查
+            Some(listed) => listed.remove(&downloader).unwrap_or_else(|| Ok(vec![])),
+            None => Downloader::from(client.clone()).download_list().await,
+        };
+        let items = match items {
+            Ok(it) => it,
+            Err(e) => {
+                report.errors.push((downloader, format!("{e:#}")));
+                continue;
+            }
+        };
+        let mut items: HashMap<_, _> = items.into_iter().map(|it| (it.id.clone(), it)).collect();
+        for model in tracked {
+            let target = rule.target(&model);
+            let item = items.remove(&model.item_id);
+            let (Some(target), Some(item)) = (target, item) else {
+                continue;
+            };
+            let decision = decide(rule, &client, &model, item, target).await;
+            report.decisions.push(decision);
+        }
+    }
+    Ok(report)
+}
+
+async fn decide(
+    rule: &SeedingRule,
+    client: &download_client::Model,
+    model: &Model,
+    item: DownloadItem,
+    target: SeedTarget,
+) -> SeedDecision {
+    // 下载器不提供做种时间时（例如 aria2），使用记录的下载完成时间计算
+    let seeding_time = item.seeding_time.or_else(|| seeding_time(model));
+    let met = target.is_met(item.ratio, seeding_time);
+    let mut decision = SeedDecision {
+        downloader: item.downloader,
+        id: item.id,
+        indexer: model.indexer,
+        ratio: item.ratio,
+        seeding_time,
+        target,
+        action: met.then_some(rule.action),
+        error: None,
+    };
+    if !met {
+        return decision;
+    }
+
+    // 只在首次达到做种目标时发布事件，dry run 或执行操作失败时之后的检查不再发布
+    if !model.target_met {
+        publish(SeedingTargetMet {
+            downloader: decision.downloader,
+            id: decision.id.clone(),
+            action: rule.action,
+            dry_run: rule.dry_run,
+        });
+        let mut model: ActiveModel = model.clone().into();
+        model.target_met = Set(true);
+        if let Err(e) = model.update(database()).await {
+            decision.error = Some(format!("{e:#}"));
+        }
+    }
+    if !rule.dry_run {
+        let apply = apply(rule.action, client, model).await;
+        decision.error = apply.err().map(|e| format!("{e:#}"));
+    }
+    decision
+}
+
+/// 从记录的下载完成时间到现在的时间
+fn seeding_time(model: &Model) -> Option<Duration> {
+    let completed_at = u64::try_from(model.completed_at?).ok()?;
+    let completed_at = UNIX_EPOCH + Duration::from_secs(completed_at);
+    SystemTime::now().duration_since(completed_at).ok()
+}
+
+/// 执行操作，成功后标记下载项已处理，之后不再检查
+async fn apply(action: SeedAction, client: &download_client::Model, model: &Model) -> Result<()> {
+    let downloader = Downloader::from(client.clone());
+    let id = model.item_id.as_str();
+    match action {
+        SeedAction::Pause => downloader.pause(id).await?,
+        SeedAction::Remove => downloader.remove(id, false).await?,
+        SeedAction::RemoveWithData => downloader.remove(id, true).await?,
+    }
+    let mut model: ActiveModel = model.clone().into();
+    model.seeded = Set(true);
+    model.update(database()).await?;
+    Ok(())
+}
@@ -1,20 +1,26 @@
-use crate::{DownloadItem, DEFAULT_CATEGORY};
+use crate::mapping::PathMapper;
+use crate::{
+    message, progress, timestamp, DownloadFile, DownloadItem, ItemStatus, TorrentLimits,
+    DEFAULT_CATEGORY,
+};
 use anyhow::{bail, ensure, Context, Result};
 use base64::Engine;
 use core::entity::download_client::Model;
 use core::request::{direct, Req, Resp, StatusCode};
 use once_cell::sync::Lazy;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, IgnoredAny};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Default)]
 struct SessionMap(Mutex<HashMap<u32, Arc<str>>>);
 
 impl SessionMap {
     fn get(&self, id: u32) -> Option<Arc<str>> {
-        self.0.lock().unwrap().get(&id).map(|it| it.clone())
+        self.0.lock().unwrap().get(&id).cloned()
     }
 
     fn set(&self, id: u32, session: &str) {
@@ -22,21 +28,34 @@ impl SessionMap {
     }
 }
 
-static SESSION: Lazy<SessionMap> = Lazy::new(|| SessionMap::default());
+static SESSION: Lazy<SessionMap> = Lazy::new(SessionMap::default);
 
-static TORRENT_FIELDS: [&str; 8] = [
+static TORRENT_FIELDS: [&str; 21] = [
     "id",
     "name",
+    "downloadDir",
     "hashString",
     "percentDone",
+    "sizeWhenDone",
+    "downloadedEver",
+    "uploadedEver",
+    "rateDownload",
     "rateUpload",
+    "uploadRatio",
+    "eta",
+    "secondsSeeding",
+    "addedDate",
+    "doneDate",
     "isFinished",
     "status",
+    "error",
+    "errorString",
     "labels",
+    "files",
 ];
 
 #[derive(Debug, Serialize)]
-#[serde(untagged, rename_all = "kebab-case")]
+#[serde(untagged)]
 enum RequestArg<'a> {
     Empty,
     GetTorrentList {
@@ -48,13 +67,69 @@ enum RequestArg<'a> {
     },
     AddTorrent {
         metainfo: String,
+        #[serde(rename = "download-dir")]
         download_dir: &'a str,
         labels: [&'a str; 1],
     },
+    AddMagnet {
+        filename: &'a str,
+        #[serde(rename = "download-dir")]
+        download_dir: &'a str,
+        labels: [&'a str; 1],
+    },
+    Torrent {
+        ids: [&'a str; 1],
+    },
+    RemoveTorrent {
+        ids: [&'a str; 1],
+        #[serde(rename = "delete-local-data")]
+        delete_local_data: bool,
+    },
+    SetLabels {
+        ids: [&'a str; 1],
+        labels: Vec<&'a str>,
+    },
+    SetLimits(TorrentLimitArgs<'a>),
+}
+
+/// 限速和做种设置参数，速度单位为 KB/s
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TorrentLimitArgs<'a> {
+    ids: [&'a str; 1],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upload_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upload_limited: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download_limited: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed_ratio_limit: Option<f64>,
+    /// 1 表示使用单独的分享率设置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed_ratio_mode: Option<u8>,
+}
+
+impl<'a> TorrentLimitArgs<'a> {
+    fn new(id: &'a str, limits: &TorrentLimits) -> Self {
+        // 字节/秒转换为 KB/s，向上取整避免限速被设置为 0
+        let kb = |it: u64| it.div_ceil(1000);
+        Self {
+            ids: [id],
+            upload_limit: limits.upload.filter(|it| *it > 0).map(kb),
+            upload_limited: limits.upload.map(|it| it > 0),
+            download_limit: limits.download.filter(|it| *it > 0).map(kb),
+            download_limited: limits.download.map(|it| it > 0),
+            seed_ratio_limit: limits.ratio,
+            seed_ratio_mode: limits.ratio.map(|_| 1),
+        }
+    }
 }
 
 /// 跳过 arg 序列化检查
-fn skip_arguments(arg: &RequestArg) -> bool {
+fn skip_arguments(arg: &&RequestArg) -> bool {
     matches!(arg, RequestArg::Empty)
 }
 
@@ -63,7 +138,7 @@ fn skip_arguments(arg: &RequestArg) -> bool {
 struct Request<'a> {
     method: &'static str,
     #[serde(skip_serializing_if = "skip_arguments")]
-    arguments: RequestArg<'a>,
+    arguments: &'a RequestArg<'a>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,17 +178,97 @@ struct TorrentList {
 struct TorrentInfo {
     id: u16,
     name: String,
+    #[serde(default)]
+    download_dir: String,
     hash_string: String,
     percent_done: f64,
+    #[serde(default)]
+    size_when_done: u64,
+    #[serde(default)]
+    downloaded_ever: u64,
+    #[serde(default)]
+    uploaded_ever: u64,
+    #[serde(default)]
+    rate_download: u64,
     rate_upload: u64,
+    /// -1 表示没有分享率，-2 表示无限
+    #[serde(default)]
+    upload_ratio: f64,
+    /// 剩余秒数，-1 表示不可用，-2 表示无法估计
+    #[serde(default)]
+    eta: i64,
+    #[serde(default)]
+    seconds_seeding: u64,
+    #[serde(default)]
+    added_date: i64,
+    /// 未完成时为 0
+    #[serde(default)]
+    done_date: i64,
     is_finished: bool,
     status: u8,
+    /// 错误类型，0 为无错误
+    error: u8,
+    #[serde(default)]
+    error_string: String,
+    #[serde(default)]
     labels: Vec<String>,
+    #[serde(default)]
+    files: Vec<TorrentFile>,
+}
+
+/// torrent 文件信息，name 为相对于 downloadDir 的路径
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TorrentFile {
+    name: String,
+    length: u64,
+    bytes_completed: u64,
 }
 
 impl TorrentInfo {
-    fn into_item(self, _id: u32, _local: &str) -> DownloadItem {
-        todo!()
+    fn into_item(self, id: u32, paths: &PathMapper) -> DownloadItem {
+        // 下载项路径为 downloadDir/name
+        let path = paths.local(Path::new(&self.download_dir).join(&self.name));
+        let downloader = id;
+        let id = self.hash_string;
+        // status: 0 停止、1 等待校验、2 校验中、3 等待下载、4 下载中、5 等待做种、6 做种中
+        let status = match self.status {
+            _ if path.is_none() || self.error != 0 => ItemStatus::Error,
+            // 已完成的下载项重新校验时保持下载完成，避免再次触发下载完成事件
+            1 | 2 if self.done_date > 0 => ItemStatus::Downloaded,
+            0..=6 if self.percent_done < 1.0 => ItemStatus::Downloading,
+            1 | 2 => ItemStatus::Downloading,
+            // 达到做种限制后会停止并标记为完成
+            0 if self.is_finished => ItemStatus::Complete,
+            0 | 3..=6 => ItemStatus::Downloaded,
+            _ => ItemStatus::Error,
+        };
+
+        let mut item = DownloadItem::new(downloader, id, status, path.unwrap_or_default());
+        item.progress = self.percent_done;
+        item.size = self.size_when_done;
+        item.downloaded = self.downloaded_ever;
+        item.uploaded = self.uploaded_ever;
+        item.download_speed = self.rate_download;
+        item.upload_speed = self.rate_upload;
+        item.ratio = self.upload_ratio.max(0.0);
+        let eta = u64::try_from(self.eta).ok().filter(|it| *it > 0);
+        item.eta = eta.map(Duration::from_secs);
+        let seeding_time = Some(self.seconds_seeding).filter(|it| *it > 0);
+        item.seeding_time = seeding_time.map(Duration::from_secs);
+        item.added_at = timestamp(self.added_date);
+        item.completed_at = timestamp(self.done_date);
+        item.message = message(self.error_string);
+        let dir = Path::new(&self.download_dir);
+        let files = self.files.into_iter().filter_map(|it| {
+            Some(DownloadFile {
+                path: paths.local(dir.join(&it.name))?,
+                size: it.length,
+                progress: progress(it.bytes_completed, it.length),
+            })
+        });
+        item.files = files.collect();
+        item
     }
 }
 
@@ -123,7 +278,7 @@ pub struct Client {
     username: Option<String>,
     password: Option<String>,
     download_dir: String,
-    local_dir: String,
+    paths: PathMapper,
     category: Arc<str>,
 }
 
@@ -135,22 +290,70 @@ impl Client {
     pub(crate) async fn download(&self, torrent: &[u8]) -> Result<DownloadItem> {
         let id = self.add_torrent(torrent).await?;
         let info = self.torrent_info(&id).await?;
-        Ok(info.into_item(self.id, &self.local_dir))
+        Ok(info.into_item(self.id, &self.paths))
+    }
+
+    pub(crate) async fn download_magnet(&self, magnet: &str) -> Result<DownloadItem> {
+        let req = RequestArg::AddMagnet {
+            filename: magnet,
+            download_dir: self.download_dir.as_str(),
+            labels: [self.category.as_ref()],
+        };
+        let id = self.add(req).await?;
+        let info = self.torrent_info(&id).await?;
+        Ok(info.into_item(self.id, &self.paths))
     }
 
     pub(crate) async fn download_list(&self) -> Result<Vec<DownloadItem>> {
         let list = self.torrent_list().await?;
         let list = list
             .into_iter()
-            .map(|it| it.into_item(self.id, &self.local_dir));
+            .map(|it| it.into_item(self.id, &self.paths));
         Ok(list.collect())
     }
+
+    pub(crate) async fn pause(&self, id: &str) -> Result<()> {
+        let req = RequestArg::Torrent { ids: [id] };
+        self.rpc_without_resp("torrent-stop", req).await
+    }
+
+    pub(crate) async fn resume(&self, id: &str) -> Result<()> {
+        let req = RequestArg::Torrent { ids: [id] };
+        self.rpc_without_resp("torrent-start", req).await
+    }
+
+    pub(crate) async fn remove(&self, id: &str, delete_local_data: bool) -> Result<()> {
+        let req = RequestArg::RemoveTorrent {
+            ids: [id],
+            delete_local_data,
+        };
+        self.rpc_without_resp("torrent-remove", req).await
+    }
+
+    pub(crate) async fn recheck(&self, id: &str) -> Result<()> {
+        let req = RequestArg::Torrent { ids: [id] };
+        self.rpc_without_resp("torrent-verify", req).await
+    }
+
+    /// 设置 label 会覆盖原有的全部 label，需要保留用于跟踪下载项的 label
+    pub(crate) async fn set_category(&self, id: &str, category: &str) -> Result<()> {
+        let mut labels = vec![self.category.as_ref()];
+        if category != self.category.as_ref() {
+            labels.push(category);
+        }
+        let req = RequestArg::SetLabels { ids: [id], labels };
+        self.rpc_without_resp("torrent-set", req).await
+    }
+
+    pub(crate) async fn set_limits(&self, id: &str, limits: &TorrentLimits) -> Result<()> {
+        let req = RequestArg::SetLimits(TorrentLimitArgs::new(id, limits));
+        self.rpc_without_resp("torrent-set", req).await
+    }
 }
 
 impl Client {
     async fn port_test(&self) -> Result<()> {
-        let req = self.build_req("port-test", RequestArg::Empty);
-        let resp: PortTestResp = self.rpc(req).await?;
+        let resp: PortTestResp = self.rpc("port-test", RequestArg::Empty).await?;
         ensure!(resp.port_is_open, "Can't connect transmission_old port.");
         Ok(())
     }
@@ -161,8 +364,12 @@ impl Client {
             download_dir: self.download_dir.as_str(),
             labels: [self.category.as_ref()],
         };
-        let req = self.build_req("torrent-add", req);
-        let resp: AddTorrentResp = self.rpc(req).await?;
+        self.add(req).await
+    }
+
+    /// 添加 torrent，返回 info hash
+    async fn add(&self, req: RequestArg<'_>) -> Result<String> {
+        let resp: AddTorrentResp = self.rpc("torrent-add", req).await?;
         let id = match resp {
             AddTorrentResp::TorrentAdded(it) => it.hash_string,
             AddTorrentResp::TorrentDuplicate(it) => it.hash_string,
@@ -174,18 +381,13 @@ impl Client {
         let req = RequestArg::GetTorrentList {
             fields: &TORRENT_FIELDS,
         };
-        let req = self.build_req("torrent-get", req);
-        let resp: TorrentList = self.rpc(req).await?;
+        let resp: TorrentList = self.rpc("torrent-get", req).await?;
         // FIXME: transmission rpc api 并未提供过滤，此处会获取所有 torrent 列表
         // TODO 需要根据 api 进行调用方法的切换
         let list = resp.torrents.into_iter();
 
-        let list = list.filter(|it| {
-            it.labels
-                .iter()
-                .find(|it| *it == self.category.as_ref())
-                .is_some()
-        });
+        let category = self.category.as_ref();
+        let list = list.filter(|it| it.labels.iter().any(|it| it == category));
         Ok(list.collect())
     }
 
@@ -194,8 +396,7 @@ impl Client {
             fields: &TORRENT_FIELDS,
             ids: [id],
         };
-        let req = self.build_req("torrent-get", req);
-        let resp: TorrentList = self.rpc(req).await?;
+        let resp: TorrentList = self.rpc("torrent-get", req).await?;
         let info = resp.torrents.into_iter().next();
         info.context("Can't find torrent.")
     }
@@ -210,8 +411,8 @@ impl Client {
         Ok(())
     }
 
-    fn build_req(&self, method: &'static str, arguments: RequestArg) -> Req {
-        let mut req = direct().post(&self.url);
+    fn build_req(&self, method: &'static str, arguments: &RequestArg) -> Req {
+        let mut req = direct().without_rate_limit().post(&self.url);
         let username = self.username.as_ref();
         if let Some(username) = username.filter(|it| !it.is_empty()) {
             let password = self.password.as_ref();
@@ -223,15 +424,27 @@ impl Client {
         req.json(&Request { method, arguments })
     }
 
-    async fn rpc<R: DeserializeOwned>(&self, req: Req) -> Result<R> {
-        let first_req = req.clone();
-        let resp = first_req.send().await?;
+    async fn rpc_without_resp(
+        &self,
+        method: &'static str,
+        arguments: RequestArg<'_>,
+    ) -> Result<()> {
+        let _: IgnoredAny = self.rpc(method, arguments).await?;
+        Ok(())
+    }
+
+    async fn rpc<R: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        arguments: RequestArg<'_>,
+    ) -> Result<R> {
+        let resp = self.build_req(method, &arguments).send().await?;
 
-        // 如果鉴权失败并返回 session 那么先设置 session 后再次尝试
+        // session 不存在或过期时返回 409 和新的 session，设置 session 后使用新的 session 重新请求
         let resp = match resp.status() {
             StatusCode::CONFLICT => {
                 self.update_session(&resp)?;
-                let resp = req.send().await;
+                let resp = self.build_req(method, &arguments).send().await;
                 resp.and_then(|it| it.error_for_status())?
             }
             _ => resp.error_for_status()?,
@@ -247,13 +460,14 @@ impl Client {
 
 impl From<Model> for Client {
     fn from(value: Model) -> Self {
+        let paths = PathMapper::from(&value);
         Self {
             id: value.id,
             url: value.url,
             username: value.username,
             password: value.password,
             download_dir: value.download_dir,
-            local_dir: value.local_dir,
+            paths,
             category: value
                 .category
                 .map(|it| it.into())
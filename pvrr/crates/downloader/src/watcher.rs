@@ -0,0 +1,144 @@
+use crate::seeding::check_listed;
+use crate::{DownloadItem, Downloader};
+use anyhow::Result;
+use core::database::{database, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use core::entity::download_client;
+use core::entity::download_item::{ActiveModel, Column, Entity, ItemStatus, Model};
+use core::event::{publish, DownloadCompleted, DownloadStatusChanged};
+use core::setting::{DOWNLOAD_WATCH_INTERVAL, SEEDING_RULE};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// 启动后台任务，按设置的间隔轮询全部下载器，启用做种规则时在轮询后检查做种目标
+pub fn spawn_watcher() -> JoinHandle<()> {
+    tokio::spawn(async {
+        loop {
+            // 轮询失败时会在下次轮询时重试，做种检查使用轮询获取的下载项列表
+            let listed = poll().await;
+            let rule = SEEDING_RULE.get().await;
+            if let (Ok(listed), Some(rule)) = (listed, rule.ok().filter(|it| it.enabled)) {
+                let _ = check_listed(&rule, Some(listed.into_iter().collect())).await;
+            }
+            let interval = DOWNLOAD_WATCH_INTERVAL.get().await;
+            let interval = interval.unwrap_or_else(|_| DOWNLOAD_WATCH_INTERVAL.default_value());
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+        }
+    })
+}
+
+/// 轮询全部下载器一次，与上次记录的状态对比并发布状态变化事件，
+/// 单个下载器获取失败不影响其他下载器，返回第一个错误
+pub async fn watch_once() -> Result<()> {
+    for (_, list) in poll().await? {
+        list?;
+    }
+    Ok(())
+}
+
+/// 轮询全部下载器一次，返回各下载器的下载项列表，获取或同步失败时为错误
+pub(crate) async fn poll() -> Result<Vec<(u32, Result<Vec<DownloadItem>>)>> {
+    let clients = download_client::Entity::find().all(database()).await?;
+    let mut listed = Vec::with_capacity(clients.len());
+    for client in clients {
+        let id = client.id;
+        let list = async {
+            let items = Downloader::from(client).download_list().await?;
+            sync(id, &items).await?;
+            Ok(items)
+        };
+        listed.push((id, list.await));
+    }
+    Ok(listed)
+}
+
+/// 记录新添加的下载项，已记录时不做处理
+pub(crate) async fn track(item: &DownloadItem) -> Result<()> {
+    let tracked = Entity::find()
+        .filter(Column::Downloader.eq(item.downloader))
+        .filter(Column::ItemId.eq(item.id.as_str()))
+        .one(database())
+        .await?;
+    if tracked.is_none() {
+        insert(item).await?;
+    }
+    Ok(())
+}
+
+/// 同步下载器的下载项列表
+async fn sync(downloader: u32, items: &[DownloadItem]) -> Result<()> {
+    let tracked = Entity::find().filter(Column::Downloader.eq(downloader));
+    let tracked = tracked.all(database()).await?.into_iter();
+    let mut tracked: HashMap<_, _> = tracked.map(|it| (it.item_id.clone(), it)).collect();
+
+    for item in items {
+        let model = match tracked.remove(&item.id) {
+            Some(it) => it,
+            None => {
+                insert(item).await?;
+                changed(item, None);
+                continue;
+            }
+        };
+        let old = model.status;
+        let path = item.path.to_string_lossy();
+        let completed = model.completed_at.is_none() && item.status.is_finished();
+        if old == item.status && model.path == path && !completed {
+            continue;
+        }
+        let mut model: ActiveModel = model.into();
+        model.status = Set(item.status);
+        model.path = Set(path.into_owned());
+        if completed {
+            model.completed_at = Set(Some(completed_at(item)));
+        }
+        model.update(database()).await?;
+        if old != item.status {
+            changed(item, Some(old));
+        }
+    }
+
+    // 下载器中已删除的下载项不再跟踪
+    for model in tracked.into_values() {
+        Entity::delete_by_id(model.id).exec(database()).await?;
+    }
+    Ok(())
+}
+
+async fn insert(item: &DownloadItem) -> Result<Model> {
+    let model = ActiveModel {
+        downloader: Set(item.downloader),
+        item_id: Set(item.id.clone()),
+        status: Set(item.status),
+        path: Set(item.path.to_string_lossy().into_owned()),
+        completed_at: Set(item.status.is_finished().then(|| completed_at(item))),
+        ..Default::default()
+    };
+    Ok(model.insert(database()).await?)
+}
+
+/// 下载完成时间，下载器不提供时使用首次发现下载完成的时间
+fn completed_at(item: &DownloadItem) -> i64 {
+    let time = item.completed_at.unwrap_or_else(SystemTime::now);
+    let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    time.as_secs() as i64
+}
+
+/// 发布状态变化事件，由下载中或错误变为下载完成时发布下载完成事件，
+/// 首次发现的下载项无法判断何时完成，因此不发布下载完成事件
+fn changed(item: &DownloadItem, old: Option<ItemStatus>) {
+    publish(DownloadStatusChanged {
+        downloader: item.downloader,
+        id: item.id.clone(),
+        old,
+        new: item.status,
+    });
+    let unfinished = old.is_some_and(|it| !it.is_finished());
+    if unfinished && item.status.is_finished() {
+        publish(DownloadCompleted {
+            downloader: item.downloader,
+            id: item.id.clone(),
+            path: item.path.clone(),
+        });
+    }
+}
@@ -0,0 +1,59 @@
+use core::database::{database, load_memory, ActiveModelTrait, ConnectionTrait, EntityTrait, Set};
+use core::entity::{download_client, download_item};
+use downloader::{watch_once, Downloader, ItemStatus};
+use serde_json::json;
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn added_track_error_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let url = stub_server(|req| {
+            let path = req.line.split(' ').nth(1).unwrap_or_default();
+            let body = match path {
+                "/api/v2/torrents/info?category=pvrr" => json!([{
+                    "hash": "1ec0dbd01cfd4150b113bd95c4f02435e3a4d270",
+                    "state": "downloading",
+                    "content_path": "/downloads/movie.mkv",
+                }]),
+                _ => json!([]),
+            };
+            StubResp::from((200, body.to_string()))
+        })
+        .await;
+        let client = download_client::ActiveModel {
+            cat: Set(download_client::Category::Qbittorrent),
+            name: Set("qbittorrent".to_owned()),
+            url: Set(url),
+            download_dir: Set("/downloads".to_owned()),
+            local_dir: Set("/data".to_owned()),
+            ..Default::default()
+        };
+        let client = client.insert(database()).await.unwrap();
+
+        // 记录下载项失败时，下载器中已添加的下载项仍然返回
+        let rename = |from: &str, to: &str| format!("ALTER TABLE {from} RENAME TO {to}");
+        let broken = rename("download_item", "download_item_bak");
+        database().execute_unprepared(&broken).await.unwrap();
+        let magnet = "magnet:?xt=urn:btih:1ec0dbd01cfd4150b113bd95c4f02435e3a4d270";
+        let item = Downloader::from(client).download_magnet(magnet).await;
+        assert_eq!(item.unwrap().status, ItemStatus::Downloading);
+
+        // 下次轮询时记录
+        let restore = rename("download_item_bak", "download_item");
+        database().execute_unprepared(&restore).await.unwrap();
+        assert!(download_item::Entity::find()
+            .one(database())
+            .await
+            .unwrap()
+            .is_none());
+        watch_once().await.unwrap();
+        let tracked = download_item::Entity::find().one(database()).await.unwrap();
+        assert_eq!(
+            tracked.unwrap().item_id,
+            "1ec0dbd01cfd4150b113bd95c4f02435e3a4d270"
+        );
+    });
+}
@@ -0,0 +1,72 @@
+use core::database::{database, load_memory, ActiveModelTrait, EntityTrait, Set};
+use core::entity::{download_client, download_item};
+use core::seeding::{SeedAction, SeedingRule};
+use downloader::{check_seeding_with, watch_once};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn aria2_seeding_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let url = stub_server(|req| {
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let result = match param["method"].as_str().unwrap() {
+                "aria2.tellActive" => json!([{
+                    "gid": "1",
+                    "totalLength": "1000",
+                    "completedLength": "1000",
+                    "uploadLength": "500",
+                    "status": "active",
+                    "dir": "/downloads",
+                    "files": [{ "path": "/downloads/movie.mkv", "length": "1000", "completedLength": "1000" }],
+                    "infoHash": "abc",
+                }]),
+                _ => json!([]),
+            };
+            StubResp::from((200, json!({ "result": result }).to_string()))
+        })
+        .await;
+
+        let client = download_client::ActiveModel {
+            cat: Set(download_client::Category::Aira2),
+            name: Set("aria2".to_owned()),
+            url: Set(format!("{url}/jsonrpc")),
+            download_dir: Set("/downloads".to_owned()),
+            local_dir: Set("/data".to_owned()),
+            ..Default::default()
+        };
+        client.insert(database()).await.unwrap();
+
+        // aria2 不提供做种时间，记录首次发现下载完成的时间
+        watch_once().await.unwrap();
+        let item = download_item::Entity::find().one(database()).await.unwrap();
+        let item = item.unwrap();
+        assert_eq!(item.item_id, "abc");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let completed_at = item.completed_at.unwrap();
+        assert!(now.as_secs() as i64 - completed_at < 60);
+
+        let rule = SeedingRule {
+            enabled: true,
+            dry_run: true,
+            action: SeedAction::Pause,
+            minimum_seed_time: Some(3600),
+            ..Default::default()
+        };
+        let report = check_seeding_with(&rule).await.unwrap();
+        assert_eq!(report.decisions[0].action, None);
+
+        // 使用记录的完成时间计算做种时间
+        let mut item: download_item::ActiveModel = item.into();
+        item.completed_at = Set(Some(completed_at - 7200));
+        item.update(database()).await.unwrap();
+        let report = check_seeding_with(&rule).await.unwrap();
+        let decision = &report.decisions[0];
+        assert_eq!(decision.action, Some(SeedAction::Pause));
+        assert!(decision.seeding_time >= Some(Duration::from_secs(7200)));
+    });
+}
@@ -0,0 +1,133 @@
+use core::database::{database, load_memory, ActiveModelTrait, EntityTrait};
+use core::entity::{download_client, download_item};
+use downloader::{watch_once, Downloader};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+async fn client(id: u32, cat: download_client::Category, url: String) -> download_client::Model {
+    let client = download_client::ActiveModel::from(download_client_model(id, cat, url));
+    client.insert(database()).await.unwrap()
+}
+
+/// 模拟 Transmission，torrent-set 会覆盖 labels
+async fn transmission(labels: &Arc<Mutex<Vec<String>>>) -> String {
+    let labels = labels.clone();
+    stub_server(move |req| {
+        if req.header("X-Transmission-Session-Id").is_none() {
+            return StubResp {
+                status: 409,
+                headers: vec![("X-Transmission-Session-Id", "session".to_owned())],
+                body: String::new(),
+            };
+        }
+        let param: Value = serde_json::from_str(&req.body).unwrap();
+        let arguments = match param["method"].as_str().unwrap() {
+            "torrent-set" => {
+                let set = param["arguments"]["labels"].as_array().unwrap();
+                let set = set.iter().map(|it| it.as_str().unwrap().to_owned());
+                *labels.lock().unwrap() = set.collect();
+                json!({})
+            }
+            _ => json!({ "torrents": [{
+                "id": 1,
+                "name": "movie.mkv",
+                "hashString": "abc",
+                "percentDone": 0.5,
+                "rateUpload": 0,
+                "isFinished": false,
+                "status": 4,
+                "error": 0,
+                "labels": *labels.lock().unwrap(),
+            }] }),
+        };
+        let body = json!({ "result": "success", "arguments": arguments });
+        StubResp::from((200, body.to_string()))
+    })
+    .await
+}
+
+/// 模拟 qBittorrent，设置分类后按 pvrr 分类获取列表时不再返回，标签按请求添加或移除
+async fn qbittorrent(category: &Arc<Mutex<String>>, tags: &Arc<Mutex<Vec<String>>>) -> String {
+    let (category, tags) = (category.clone(), tags.clone());
+    stub_server(move |req| {
+        let path = req.line.split(' ').nth(1).unwrap_or_default();
+        let body = match path.split_once('?').map_or(path, |it| it.0) {
+            "/api/v2/torrents/info" => {
+                let category = category.lock().unwrap().clone();
+                match path.ends_with(&format!("category={category}")) {
+                    true => json!([{ "hash": "def", "state": "downloading", "progress": 0.5 }]),
+                    false => json!([]),
+                }
+            }
+            "/api/v2/torrents/setCategory" => {
+                let form = req
+                    .body
+                    .split('&')
+                    .find_map(|it| it.strip_prefix("category="));
+                *category.lock().unwrap() = form.unwrap_or_default().to_owned();
+                json!({})
+            }
+            // 为空时移除全部标签
+            "/api/v2/torrents/removeTags" => {
+                let form = req.body.split('&').find_map(|it| it.strip_prefix("tags="));
+                let remove: Vec<_> = form.unwrap_or_default().split(',').collect();
+                let mut tags = tags.lock().unwrap();
+                tags.retain(|it| !remove.contains(&"") && !remove.contains(&it.as_str()));
+                json!({})
+            }
+            "/api/v2/torrents/addTags" => {
+                let form = req.body.split('&').find_map(|it| it.strip_prefix("tags="));
+                let add = form.unwrap_or_default().split(',').map(str::to_owned);
+                tags.lock().unwrap().extend(add);
+                json!({})
+            }
+            _ => json!([]),
+        };
+        StubResp::from((200, body.to_string()))
+    })
+    .await
+}
+
+#[test]
+fn set_category_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let labels = Arc::new(Mutex::new(vec!["pvrr".to_owned()]));
+        let url = transmission(&labels).await;
+        let transmission = client(1, download_client::Category::Transmission, url).await;
+        let category = Arc::new(Mutex::new("pvrr".to_owned()));
+        // 用户添加的标签
+        let tags = Arc::new(Mutex::new(vec!["keep".to_owned()]));
+        let url = qbittorrent(&category, &tags).await;
+        let qbittorrent = client(2, download_client::Category::Qbittorrent, url).await;
+
+        let tracked = || async {
+            let list = download_item::Entity::find().all(database()).await.unwrap();
+            let mut list: Vec<_> = list.into_iter().map(|it| it.item_id).collect();
+            list.sort();
+            list
+        };
+        watch_once().await.unwrap();
+        assert_eq!(tracked().await, ["abc", "def"]);
+
+        // 设置分类后仍然保留用于跟踪的分类
+        Downloader::from(transmission)
+            .set_category("abc", "movie")
+            .await
+            .unwrap();
+        Downloader::from(qbittorrent)
+            .set_category("def", "movie")
+            .await
+            .unwrap();
+        assert_eq!(*labels.lock().unwrap(), ["pvrr", "movie"]);
+        assert_eq!(*category.lock().unwrap(), "pvrr");
+        // 不影响用户添加的其他标签
+        assert_eq!(*tags.lock().unwrap(), ["keep", "movie"]);
+
+        watch_once().await.unwrap();
+        assert_eq!(tracked().await, ["abc", "def"]);
+    });
+}
@@ -0,0 +1,213 @@
+use core::database::load_memory;
+use core::entity::download_client::{Category, Model};
+use downloader::{DownloadFile, Downloader, ItemStatus};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+fn magnet(hash: &str) -> String {
+    format!("magnet:?xt=urn:btih:{}", hash.repeat(10))
+}
+
+fn torrent(name: &str, state: &str, progress: f64, is_finished: bool) -> Value {
+    json!({ "name": name, "state": state, "progress": progress, "is_finished": is_finished, "label": "pvrr" })
+}
+
+#[test]
+fn deluge_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let methods = Arc::new(Mutex::new(Vec::<String>::new()));
+        let record = methods.clone();
+        let no_label = Arc::new(AtomicBool::new(false));
+        let label_missing = no_label.clone();
+        let url = stub_server(move |req| {
+            assert!(req.line.starts_with("POST /json "));
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let method = param["method"].as_str().unwrap().to_owned();
+            record.lock().unwrap().push(method.clone());
+
+            let login = req.header("cookie") == Some("_session_id=deluge");
+            let result = match method.as_str() {
+                "auth.login" => {
+                    assert_eq!(param["params"], json!(["deluge"]));
+                    return StubResp {
+                        status: 200,
+                        headers: vec![("Set-Cookie", "_session_id=deluge; Path=/json".to_owned())],
+                        body: json!({ "result": true, "error": null, "id": 0 }).to_string(),
+                    };
+                }
+                _ if !login => {
+                    let error = json!({ "message": "Not authenticated", "code": 1 });
+                    return (
+                        200,
+                        json!({ "result": null, "error": error, "id": 1 }).to_string(),
+                    )
+                        .into();
+                }
+                "web.connected" => json!(false),
+                "web.get_hosts" => json!([["host-id", "127.0.0.1", 58846, "admin"]]),
+                "web.connect" => {
+                    assert_eq!(param["params"], json!(["host-id"]));
+                    json!([])
+                }
+                // 部分 Deluge 1.x 版本添加成功时返回 null，已存在时 Deluge 2.x 返回错误
+                "core.add_torrent_magnet" if param["params"][0] == magnet("aaaa") => Value::Null,
+                "core.add_torrent_magnet" if param["params"][0] == magnet("bbbb") => {
+                    let error = json!({ "message": "Torrent already in session (bbbb).", "code": 4 });
+                    return (
+                        200,
+                        json!({ "result": null, "error": error, "id": 1 }).to_string(),
+                    )
+                        .into();
+                }
+                "core.add_torrent_magnet" => {
+                    assert_eq!(
+                        param["params"][1],
+                        json!({ "download_location": "/downloads" })
+                    );
+                    json!("added")
+                }
+                "label.add" => {
+                    let error = match label_missing.load(Ordering::SeqCst) {
+                        true => json!({ "message": "Unknown method", "code": 2 }),
+                        false => json!({ "message": "Label already exists", "code": 4 }),
+                    };
+                    return (
+                        200,
+                        json!({ "result": null, "error": error, "id": 1 }).to_string(),
+                    )
+                        .into();
+                }
+                "label.set_torrent" => {
+                    let hash = param["params"][0].as_str().unwrap();
+                    assert!(hash == "added" || hash == "aaaa".repeat(10));
+                    assert_eq!(param["params"][1], "pvrr");
+                    Value::Null
+                }
+                "core.get_torrents_status" if param["params"][0] == json!({ "id": ["added"] }) => {
+                    let mut added = torrent("added", "Downloading", 25.0, false);
+                    let detail = json!({
+                        "save_path": "/downloads", "total_wanted": 1000, "total_done": 250,
+                        "total_uploaded": 125, "download_payload_rate": 75, "upload_payload_rate": 20,
+                        "ratio": 0.5, "eta": 10, "seeding_time": 0, "time_added": 1700000000.5,
+                        "completed_time": 0, "message": "OK", "tracker_status": "Announce OK",
+                        "files": [{ "index": 0, "path": "added/01.mkv", "size": 1000, "offset": 0 }],
+                        "file_progress": [0.25],
+                    });
+                    added.as_object_mut().unwrap().extend(detail.as_object().unwrap().clone());
+                    json!({ "added": added })
+                }
+                "core.get_torrents_status" if param["params"][0]["id"].is_array() => {
+                    let id = param["params"][0]["id"][0].as_str().unwrap();
+                    let mut exists = torrent(id, "Seeding", 100.0, true);
+                    exists["save_path"] = json!("/downloads");
+                    json!({ id: exists })
+                }
+                "core.get_torrents_status" => {
+                    assert_eq!(param["params"][0], json!({ "label": "pvrr" }));
+                    json!({
+                        "downloading": torrent("downloading", "Downloading", 50.0, false),
+                        "checking": torrent("checking", "Checking", 100.0, true),
+                        "seeding": torrent("seeding", "Seeding", 100.0, true),
+                        "complete": torrent("complete", "Paused", 100.0, true),
+                        "error": torrent("error", "Error", 10.0, false),
+                    })
+                }
+                method => panic!("unexpected method {method}"),
+            };
+            (
+                200,
+                json!({ "result": result, "error": null, "id": 1 }).to_string(),
+            )
+                .into()
+        })
+        .await;
+
+        let model = Model {
+            password: Some("deluge".to_owned()),
+            ..download_client_model(1, Category::Deluge, url)
+        };
+        let client = || Downloader::from(model.clone());
+
+        client().connect_test().await.unwrap();
+        assert_eq!(
+            *methods.lock().unwrap(),
+            [
+                "auth.login",
+                "web.connected",
+                "web.get_hosts",
+                "web.connect"
+            ]
+        );
+
+        let added = "magnet:?xt=urn:btih:1ec0dbd01cfd4150b113bd95c4f02435e3a4d270";
+        let item = client().download_magnet(added).await.unwrap();
+        assert_eq!(
+            (item.id.as_str(), item.status),
+            ("added", ItemStatus::Downloading)
+        );
+        assert_eq!(item.path, Path::new("/data/added"));
+        assert_eq!(item.progress, 0.25);
+        assert_eq!(
+            (item.size, item.downloaded, item.uploaded),
+            (1000, 250, 125)
+        );
+        assert_eq!((item.download_speed, item.upload_speed), (75, 20));
+        assert_eq!(item.ratio, 0.5);
+        assert_eq!(item.eta, Some(Duration::from_secs(10)));
+        let added_at = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!((item.added_at, item.completed_at), (Some(added_at), None));
+        assert_eq!(item.message.as_deref(), Some("Announce OK"));
+        let file = DownloadFile {
+            path: "/data/added/01.mkv".into(),
+            size: 1000,
+            progress: 0.25,
+        };
+        assert_eq!(item.files, [file]);
+
+        // 添加成功但不返回 hash 时使用 magnet 中的 hash 设置标签，
+        // 已存在的 torrent 返回已有的下载项，不修改标签
+        for hash in ["aaaa", "bbbb"] {
+            methods.lock().unwrap().clear();
+            let item = client().download_magnet(&magnet(hash)).await.unwrap();
+            assert_eq!(item.id, hash.repeat(10));
+            assert_eq!(item.status, ItemStatus::Downloaded);
+            let set_label = methods
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|it| it == "label.set_torrent");
+            assert_eq!(set_label, hash == "aaaa");
+        }
+
+        // 未启用 Label 插件时下载项不会出现在列表中，返回错误
+        no_label.store(true, Ordering::SeqCst);
+        let error = client().download_magnet(added).await.unwrap_err();
+        assert!(format!("{error:#}").contains("Unknown method"));
+        no_label.store(false, Ordering::SeqCst);
+
+        let mut list: Vec<_> = client()
+            .download_list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|it| (it.id, it.status))
+            .collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        let expect = [
+            ("checking", ItemStatus::Downloading),
+            ("complete", ItemStatus::Complete),
+            ("downloading", ItemStatus::Downloading),
+            ("error", ItemStatus::Error),
+            ("seeding", ItemStatus::Downloaded),
+        ];
+        let expect: Vec<_> = expect.map(|(id, status)| (id.to_owned(), status)).into();
+        assert_eq!(list, expect);
+    });
+}
@@ -0,0 +1,159 @@
+use core::entity::download_client::Category;
+use downloader::{DownloadFile, Downloader, ItemStatus};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn aria2_item_test() {
+    block_on(async {
+        let url = stub_server(|req| {
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let result = match param["method"].as_str().unwrap() {
+                "aria2.tellActive" => json!([{
+                    "gid": "1",
+                    "totalLength": "1000",
+                    "completedLength": "250",
+                    "uploadLength": "125",
+                    "downloadSpeed": "75",
+                    "uploadSpeed": "20",
+                    "status": "active",
+                    "dir": "/downloads",
+                    "files": [
+                        { "path": "/downloads/show/01.mkv", "length": "600", "completedLength": "150" },
+                        { "path": "/downloads/show/02.mkv", "length": "400", "completedLength": "100" },
+                    ],
+                }]),
+                "aria2.tellStopped" => json!([{
+                    "gid": "2",
+                    "totalLength": "100",
+                    "completedLength": "10",
+                    "status": "error",
+                    "errorMessage": "Network problem has occurred.",
+                    "dir": "/downloads",
+                    "files": [{ "path": "/downloads/error.mkv", "length": "100", "completedLength": "10" }],
+                }]),
+                _ => json!([]),
+            };
+            StubResp::from((200, json!({ "result": result }).to_string()))
+        })
+        .await;
+
+        let list = Downloader::from(download_client_model(1, Category::Aira2, url));
+        let list = list.download_list().await.unwrap();
+        let [item, error] = list.as_slice() else {
+            panic!("unexpected list {list:?}");
+        };
+        assert_eq!(item.status, ItemStatus::Downloading);
+        assert_eq!(item.path, Path::new("/data/show"));
+        assert_eq!(item.progress, 0.25);
+        assert_eq!(
+            (item.size, item.downloaded, item.uploaded),
+            (1000, 250, 125)
+        );
+        assert_eq!((item.download_speed, item.upload_speed), (75, 20));
+        assert_eq!(item.ratio, 0.5);
+        assert_eq!(item.eta, Some(Duration::from_secs(10)));
+        assert_eq!(item.message, None);
+        let files = [
+            DownloadFile {
+                path: "/data/show/01.mkv".into(),
+                size: 600,
+                progress: 0.25,
+            },
+            DownloadFile {
+                path: "/data/show/02.mkv".into(),
+                size: 400,
+                progress: 0.25,
+            },
+        ];
+        assert_eq!(item.files, files);
+
+        assert_eq!(error.status, ItemStatus::Error);
+        let message = error.message.as_deref();
+        assert_eq!(message, Some("Network problem has occurred."));
+    });
+}
+
+#[test]
+fn qbittorrent_item_test() {
+    block_on(async {
+        let url = stub_server(|req| {
+            let body = match req.line.split(' ').nth(1).unwrap_or_default() {
+                "/api/v2/torrents/files?hash=abc" => json!([
+                    { "name": "show/01.mkv", "size": 600, "progress": 1.0 },
+                    { "name": "show/02.mkv", "size": 400, "progress": 0.5 },
+                ]),
+                "/api/v2/torrents/info?category=pvrr" => json!([{
+                    "hash": "abc",
+                    "state": "downloading",
+                    "content_path": "/downloads/show",
+                    "save_path": "/downloads",
+                    "progress": 0.8,
+                    "size": 1000,
+                    "downloaded": 800,
+                    "uploaded": 400,
+                    "dlspeed": 100,
+                    "upspeed": 50,
+                    "ratio": 0.5,
+                    "eta": 2,
+                    "seeding_time": 0,
+                    "added_on": 1700000000,
+                    "completion_on": -1,
+                }, {
+                    "hash": "def",
+                    "state": "uploading",
+                    "content_path": "/downloads/movie.mkv",
+                    "save_path": "/downloads",
+                    "progress": 1.0,
+                }]),
+                // 获取文件列表失败不影响下载项列表
+                "/api/v2/torrents/files?hash=def" => {
+                    return StubResp::from((500, String::new()));
+                }
+                path => panic!("unexpected path {path}"),
+            };
+            StubResp::from((200, body.to_string()))
+        })
+        .await;
+
+        let list = Downloader::from(download_client_model(1, Category::Qbittorrent, url));
+        let list = list.download_list().await.unwrap();
+        let [item, movie] = list.as_slice() else {
+            panic!("unexpected list {list:?}");
+        };
+        assert_eq!(item.status, ItemStatus::Downloading);
+        assert_eq!(item.path, Path::new("/data/show"));
+        assert_eq!(item.progress, 0.8);
+        assert_eq!(
+            (item.size, item.downloaded, item.uploaded),
+            (1000, 800, 400)
+        );
+        assert_eq!((item.download_speed, item.upload_speed), (100, 50));
+        assert_eq!(item.ratio, 0.5);
+        assert_eq!(item.eta, Some(Duration::from_secs(2)));
+        assert_eq!(item.seeding_time, None);
+        let added_at = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!((item.added_at, item.completed_at), (Some(added_at), None));
+        let files = [
+            DownloadFile {
+                path: "/data/show/01.mkv".into(),
+                size: 600,
+                progress: 1.0,
+            },
+            DownloadFile {
+                path: "/data/show/02.mkv".into(),
+                size: 400,
+                progress: 0.5,
+            },
+        ];
+        assert_eq!(item.files, files);
+        assert_eq!(item.files_error, None);
+        assert_eq!(movie.path, Path::new("/data/movie.mkv"));
+        // 获取失败时返回错误，与没有文件区分
+        assert!(movie.files.is_empty());
+        assert!(movie.files_error.as_deref().unwrap().contains("500"));
+    });
+}
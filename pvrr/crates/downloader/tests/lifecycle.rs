@@ -0,0 +1,179 @@
+use core::entity::download_client::{Category, Model};
+use downloader::{Downloader, TorrentLimits};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use test_util::{download_client_model, stub_server, StubReq, StubResp};
+use tokio_test::block_on;
+
+type Calls = Arc<Mutex<Vec<(String, String)>>>;
+
+/// 记录请求路径和请求体，返回服务地址
+async fn recorder<F>(calls: &Calls, handler: F) -> String
+where
+    F: Fn(&StubReq) -> StubResp + Send + Sync + 'static,
+{
+    let calls = calls.clone();
+    stub_server(move |req| {
+        let path = req.line.split(' ').nth(1).unwrap_or_default().to_owned();
+        let resp = handler(&req);
+        calls.lock().unwrap().push((path, req.body));
+        resp
+    })
+    .await
+}
+
+async fn lifecycle(model: &Model) -> Vec<anyhow::Result<()>> {
+    let client = || Downloader::from(model.clone());
+    let limits = TorrentLimits {
+        upload: Some(1500),
+        download: Some(0),
+        ratio: Some(2.0),
+    };
+    vec![
+        client().pause("abc").await,
+        client().resume("abc").await,
+        client().recheck("abc").await,
+        client().set_category("abc", "movie").await,
+        client().set_limits("abc", &limits).await,
+        client().remove("abc", true).await,
+    ]
+}
+
+#[test]
+fn qbittorrent_lifecycle_test() {
+    block_on(async {
+        let calls = Calls::default();
+        let url = recorder(&calls, |_| (200, String::new()).into()).await;
+        let result = lifecycle(&download_client_model(1, Category::Qbittorrent, url)).await;
+        assert!(result.iter().all(Result::is_ok));
+
+        let calls = calls.lock().unwrap();
+        let calls: Vec<_> = calls
+            .iter()
+            .map(|(p, b)| (p.as_str(), b.as_str()))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("/api/v2/torrents/pause", "hashes=abc"),
+                ("/api/v2/torrents/resume", "hashes=abc"),
+                ("/api/v2/torrents/recheck", "hashes=abc"),
+                ("/api/v2/torrents/addTags", "hashes=abc&tags=movie"),
+                ("/api/v2/torrents/setUploadLimit", "hashes=abc&limit=1500"),
+                ("/api/v2/torrents/setDownloadLimit", "hashes=abc&limit=0"),
+                (
+                    "/api/v2/torrents/setShareLimits",
+                    "hashes=abc&ratioLimit=2.0&seedingTimeLimit=-2&inactiveSeedingTimeLimit=-2"
+                ),
+                ("/api/v2/torrents/delete", "hashes=abc&deleteFiles=true"),
+            ]
+        );
+    });
+}
+
+#[test]
+fn transmission_lifecycle_test() {
+    block_on(async {
+        let calls = Calls::default();
+        let url = recorder(&calls, |req| {
+            if req.header("X-Transmission-Session-Id").is_none() {
+                return StubResp {
+                    status: 409,
+                    headers: vec![("X-Transmission-Session-Id", "session".to_owned())],
+                    body: String::new(),
+                };
+            }
+            let body = json!({ "result": "success", "arguments": {} });
+            (200, body.to_string()).into()
+        })
+        .await;
+        let result = lifecycle(&download_client_model(2, Category::Transmission, url)).await;
+        assert!(result.iter().all(Result::is_ok));
+
+        let calls = calls.lock().unwrap();
+        let calls: Vec<Value> = calls
+            .iter()
+            .skip(1)
+            .map(|it| serde_json::from_str(&it.1).unwrap())
+            .collect();
+        let ids = json!(["abc"]);
+        assert_eq!(
+            calls,
+            [
+                json!({ "method": "torrent-stop", "arguments": { "ids": ids } }),
+                json!({ "method": "torrent-start", "arguments": { "ids": ids } }),
+                json!({ "method": "torrent-verify", "arguments": { "ids": ids } }),
+                json!({ "method": "torrent-set", "arguments": { "ids": ids, "labels": ["pvrr", "movie"] } }),
+                json!({ "method": "torrent-set", "arguments": {
+                    "ids": ids,
+                    "uploadLimit": 2,
+                    "uploadLimited": true,
+                    "downloadLimited": false,
+                    "seedRatioLimit": 2.0,
+                    "seedRatioMode": 1,
+                } }),
+                json!({ "method": "torrent-remove", "arguments": { "ids": ids, "delete-local-data": true } }),
+            ]
+        );
+    });
+}
+
+#[test]
+fn aria2_lifecycle_test() {
+    block_on(async {
+        let dir = std::env::temp_dir().join(format!("pvrr-aria2-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("movie")).unwrap();
+        std::fs::write(dir.join("movie/movie.mkv"), b"pvrr").unwrap();
+        std::fs::write(dir.join("movie.aria2"), b"pvrr").unwrap();
+
+        let calls = Calls::default();
+        let url = recorder(&calls, |req| {
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let result = match param["method"].as_str().unwrap() {
+                "aria2.tellStatus" => json!({
+                    "gid": "abc",
+                    "totalLength": 100,
+                    "completedLength": 100,
+                    "status": "active",
+                    "dir": "/downloads",
+                    "files": [{ "path": "/downloads/movie/movie.mkv" }],
+                }),
+                _ => json!("OK"),
+            };
+            (200, json!({ "result": result }).to_string()).into()
+        })
+        .await;
+        let mut model = download_client_model(3, Category::Aira2, url);
+        model.local_dir = dir.to_string_lossy().into_owned();
+        let result = lifecycle(&model).await;
+
+        // aria2 不支持校验和分类
+        let ok: Vec<_> = result.iter().map(Result::is_ok).collect();
+        assert_eq!(ok, [true, true, false, false, true, true]);
+        assert!(!dir.join("movie").exists());
+        assert!(!dir.join("movie.aria2").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let calls = calls.lock().unwrap();
+        let calls: Vec<Value> = calls
+            .iter()
+            .map(|it| serde_json::from_str::<Value>(&it.1).unwrap())
+            .map(|it| json!([it["method"], it["params"]]))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                json!(["aria2.pause", ["abc"]]),
+                json!(["aria2.unpause", ["abc"]]),
+                json!(["aria2.changeOption", ["abc", {
+                    "max-upload-limit": "1500",
+                    "max-download-limit": "0",
+                    "seed-ratio": "2",
+                }]]),
+                json!(["aria2.tellStatus", ["abc"]]),
+                json!(["aria2.forceRemove", ["abc"]]),
+                json!(["aria2.removeDownloadResult", ["abc"]]),
+            ]
+        );
+    });
+}
@@ -0,0 +1,140 @@
+use core::database::load_memory;
+use core::entity::download_client::Category;
+use downloader::{Downloader, ItemStatus};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+const HASH: &str = "1ec0dbd01cfd4150b113bd95c4f02435e3a4d270";
+const MAGNET: &str = "magnet:?xt=urn:btih:1EC0DBD01CFD4150B113BD95C4F02435E3A4D270&dn=movie";
+
+async fn aria2_magnet() {
+    let calls = Arc::new(Mutex::new(Vec::<Value>::new()));
+    let record = calls.clone();
+    let url = stub_server(move |req| {
+        let param: Value = serde_json::from_str(&req.body).unwrap();
+        record.lock().unwrap().push(param.clone());
+        let metadata = |status: &str, followed_by: Value| {
+            json!({
+                "gid": "meta", "totalLength": 0, "completedLength": 0, "status": status,
+                "dir": "/downloads", "files": [{ "path": format!("[METADATA]{HASH}") }],
+                "infoHash": HASH, "followedBy": followed_by,
+            })
+        };
+        let result = match param["method"].as_str().unwrap() {
+            "aria2.addUri" => json!("meta"),
+            "aria2.tellStatus" => metadata("active", json!([])),
+            "aria2.tellActive" => json!([{
+                "gid": "real", "totalLength": 100, "completedLength": 10, "status": "active",
+                "dir": "/downloads", "files": [{ "path": "/downloads/movie.mkv" }],
+                "infoHash": HASH,
+            }]),
+            "aria2.tellStopped" => json!([metadata("complete", json!(["real"]))]),
+            _ => json!([]),
+        };
+        StubResp::from((200, json!({ "result": result }).to_string()))
+    })
+    .await;
+    let model = download_client_model(1, Category::Aira2, url);
+    let client = || Downloader::from(model.clone());
+
+    let item = client().download_magnet(MAGNET).await.unwrap();
+    assert_eq!(
+        (item.id.as_str(), item.status),
+        (HASH, ItemStatus::Downloading)
+    );
+    assert_eq!(item.path, Path::new(""));
+    let add = calls.lock().unwrap()[0].clone();
+    assert_eq!(add["params"], json!([[MAGNET], { "dir": "/downloads" }]));
+
+    // 元数据下载完成后使用相同的 info hash 跟踪实际下载
+    let list = client().download_list().await.unwrap();
+    let list: Vec<_> = list.into_iter().map(|it| (it.id, it.path)).collect();
+    assert_eq!(
+        list,
+        [(HASH.to_owned(), Path::new("/data/movie.mkv").into())]
+    );
+
+    client().pause(HASH).await.unwrap();
+    let pause = calls.lock().unwrap().last().unwrap().clone();
+    assert_eq!(pause["method"], "aria2.pause");
+    assert_eq!(pause["params"], json!(["real"]));
+}
+
+async fn qbittorrent_magnet() {
+    let url = stub_server(|req| {
+        if req.line.starts_with("POST /api/v2/torrents/add") {
+            let body = format!(
+                "urls={}&savepath=%2Fdownloads&category=pvrr",
+                MAGNET
+                    .replace(':', "%3A")
+                    .replace('?', "%3F")
+                    .replace('=', "%3D")
+                    .replace('&', "%26")
+            );
+            assert_eq!(req.body, body);
+        }
+        // 元数据获取前还未列出下载项
+        (200, "[]".to_owned())
+    })
+    .await;
+
+    let client = Downloader::from(download_client_model(2, Category::Qbittorrent, url));
+    let item = client.download_magnet(MAGNET).await.unwrap();
+    assert_eq!(
+        (item.id.as_str(), item.status),
+        (HASH, ItemStatus::Downloading)
+    );
+}
+
+async fn transmission_magnet() {
+    let url = stub_server(|req| {
+        if req.header("X-Transmission-Session-Id").is_none() {
+            return StubResp {
+                status: 409,
+                headers: vec![("X-Transmission-Session-Id", "session".to_owned())],
+                body: String::new(),
+            };
+        }
+        let param: Value = serde_json::from_str(&req.body).unwrap();
+        let arguments = match param["method"].as_str().unwrap() {
+            "torrent-add" => {
+                let expect = json!({
+                    "filename": MAGNET,
+                    "download-dir": "/downloads",
+                    "labels": ["pvrr"],
+                });
+                assert_eq!(param["arguments"], expect);
+                json!({ "torrent-added": { "id": 1, "name": "movie", "hashString": HASH } })
+            }
+            _ => json!({ "torrents": [{
+                "id": 1, "name": "movie", "hashString": HASH, "percentDone": 0.0,
+                "rateUpload": 0, "isFinished": false, "status": 4, "error": 0, "labels": ["pvrr"],
+            }]}),
+        };
+        let body = json!({ "result": "success", "arguments": arguments });
+        StubResp::from((200, body.to_string()))
+    })
+    .await;
+
+    let client = Downloader::from(download_client_model(3, Category::Transmission, url));
+    let item = client.download_magnet(MAGNET).await.unwrap();
+    assert_eq!(
+        (item.id.as_str(), item.status),
+        (HASH, ItemStatus::Downloading)
+    );
+    assert_eq!(item.path, Path::new("/data/movie"));
+}
+
+/// 全局数据库在不同 runtime 间无法共享连接，因此在同一测试中依次执行
+#[test]
+fn magnet_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+        aria2_magnet().await;
+        qbittorrent_magnet().await;
+        transmission_magnet().await;
+    });
+}
@@ -0,0 +1,89 @@
+use core::database::{database, load_memory, ActiveModelTrait, EntityTrait, Set};
+use core::entity::download_client::{self, Category, PathMapping, PathMappings};
+use downloader::Downloader;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+fn mapping(remote: &str, local: &Path) -> PathMapping {
+    PathMapping {
+        remote: remote.to_owned(),
+        local: local.to_string_lossy().into_owned(),
+    }
+}
+
+#[test]
+fn path_mapping_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let dir = std::env::temp_dir().join("pvrr_path_mapping_test");
+        let (tv, movies) = (dir.join("tv"), dir.join("movies"));
+        std::fs::create_dir_all(&tv).unwrap();
+        std::fs::create_dir_all(&movies).unwrap();
+
+        let url = stub_server(|req| {
+            if req.line.starts_with("GET /api/v2/torrents/files") {
+                return StubResp::from((200, "[]".to_owned()));
+            }
+            let list = [
+                "/downloads/tv/Show/Season 1",
+                "/downloads/tvshows/show.mkv",
+                "/downloads/other.mkv",
+                "/mnt/movies/Movie (2023)/movie.mkv",
+                "/elsewhere/file.mkv",
+            ];
+            let list: Vec<_> = list
+                .iter()
+                .enumerate()
+                .map(|(i, path)| json!({ "hash": i.to_string(), "state": "uploading", "content_path": path }))
+                .collect();
+            StubResp::from((200, json!(list).to_string()))
+        })
+        .await;
+
+        let client = |mappings: Vec<PathMapping>| download_client::ActiveModel {
+            cat: Set(Category::Qbittorrent),
+            name: Set("qbittorrent".to_owned()),
+            url: Set(url.clone()),
+            download_dir: Set("/downloads".to_owned()),
+            local_dir: Set("/data".to_owned()),
+            path_mappings: Set(PathMappings(mappings)),
+            ..Default::default()
+        };
+
+        // 本地路径不存在或下载器路径重复时无法保存
+        let missing = client(vec![mapping("/downloads/tv", &dir.join("missing"))]);
+        assert!(missing.insert(database()).await.is_err());
+        let duplicated = client(vec![
+            mapping("/downloads/tv", &tv),
+            mapping("/downloads/tv/", &movies),
+        ]);
+        assert!(duplicated.insert(database()).await.is_err());
+
+        let model = client(vec![
+            mapping("/downloads/tv", &tv),
+            mapping("/mnt/movies", &movies),
+        ]);
+        let model = model.insert(database()).await.unwrap();
+        let found = download_client::Entity::find_by_id(model.id);
+        let found = found.one(database()).await.unwrap().unwrap();
+        assert_eq!(found.path_mappings, model.path_mappings);
+
+        let list = Downloader::from(found).download_list().await.unwrap();
+        let list: Vec<_> = list.into_iter().map(|it| it.path).collect();
+        let expect = [
+            tv.join("Show/Season 1"),
+            // 按路径层级匹配前缀
+            PathBuf::from("/data/tvshows/show.mkv"),
+            PathBuf::from("/data/other.mkv"),
+            movies.join("Movie (2023)/movie.mkv"),
+            // 都不匹配时使用本地下载路径
+            PathBuf::from("/data/file.mkv"),
+        ];
+        assert_eq!(list, expect);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    });
+}
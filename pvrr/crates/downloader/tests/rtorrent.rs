@@ -0,0 +1,174 @@
+use core::database::load_memory;
+use core::entity::download_client::{Category, Model};
+use downloader::{DownloadFile, Downloader, ItemStatus};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+const ADDED: &str = "1EC0DBD01CFD4150B113BD95C4F02435E3A4D270";
+
+fn response(value: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><methodResponse><params><param><value>{value}</value></param></params></methodResponse>"#
+    )
+}
+
+fn torrent(hash: &str, name: &str, label: &str, complete: u8, active: u8, message: &str) -> String {
+    let completed = if complete == 1 { 1000 } else { 250 };
+    let finished = if complete == 1 { 1700003600 } else { 0 };
+    format!(
+        "<value><array><data><value><string>{hash}</string></value><value><string>{name}</string></value>\
+        <value><string>{label}</string></value><value><i8>{complete}</i8></value>\
+        <value><i8>{active}</i8></value><value><string>{message}</string></value>\
+        <value><string>/downloads</string></value><value><i8>0</i8></value>\
+        <value><i8>1000</i8></value><value><i8>{completed}</i8></value><value><i8>500</i8></value>\
+        <value><i8>75</i8></value><value><i8>20</i8></value><value><i8>500</i8></value>\
+        <value><i8>1700000000</i8></value><value><i8>{finished}</i8></value></data></array></value>"
+    )
+}
+
+#[test]
+fn rtorrent_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let methods = Arc::new(Mutex::new(Vec::<String>::new()));
+        let record = methods.clone();
+        let url = stub_server(move |req| {
+            assert!(req.line.starts_with("POST /RPC2 "));
+            // rtorrent:rtorrent
+            let auth = req.header("authorization");
+            assert_eq!(auth, Some("Basic cnRvcnJlbnQ6cnRvcnJlbnQ="));
+            let body = req.body.as_str();
+            let start = body.find("<methodName>").unwrap() + "<methodName>".len();
+            let end = body.find("</methodName>").unwrap();
+            let method = body[start..end].to_owned();
+            record.lock().unwrap().push(method.clone());
+
+            let value = match method.as_str() {
+                "system.client_version" => "<string>0.9.8</string>".to_owned(),
+                "load.start" => {
+                    assert!(body.contains("<string>magnet:?xt=urn:btih:"));
+                    assert!(body.contains("<string>d.directory.set=&quot;/downloads&quot;</string>"));
+                    assert!(body.contains("<string>d.custom1.set=&quot;pvrr&quot;</string>"));
+                    "<i8>0</i8>".to_owned()
+                }
+                "d.multicall2" => {
+                    assert!(body.contains("<string>main</string>"));
+                    let list = [
+                        torrent(ADDED, "added", "pvrr", 0, 1, ""),
+                        torrent("AAAA", "downloading", "pvrr", 0, 1, "Tracker: timeout"),
+                        torrent("BBBB", "seeding", "pvrr", 1, 1, ""),
+                        torrent("CCCC", "complete", "pvrr", 1, 0, ""),
+                        torrent("DDDD", "error &amp; stopped", "pvrr", 0, 0, "Storage error"),
+                        torrent("EEEE", "other", "other", 1, 1, ""),
+                    ];
+                    format!("<array><data>{}</data></array>", list.concat())
+                }
+                "f.multicall" if body.contains(ADDED) => {
+                    assert!(body.contains("<string>f.path=</string>"));
+                    "<array><data><value><array><data><value><string>added</string></value>\
+                    <value><i8>1000</i8></value><value><i8>1</i8></value><value><i8>4</i8></value>\
+                    </data></array></value></data></array>"
+                        .to_owned()
+                }
+                // DDDD 获取文件列表失败，不影响下载项列表
+                "f.multicall" if !body.contains("DDDD") => "<array><data></data></array>".to_owned(),
+                "d.stop" | "d.start" | "d.erase" | "d.check_hash" => "<i8>0</i8>".to_owned(),
+                "d.custom1.set" => {
+                    assert!(body.contains("<string>pvrr</string>"));
+                    "<string>pvrr</string>".to_owned()
+                }
+                _ => {
+                    let fault = "<fault><value><struct>\
+                        <member><name>faultCode</name><value><i4>-506</i4></value></member>\
+                        <member><name>faultString</name><value><string>Method not defined</string></value></member>\
+                        </struct></value></fault>";
+                    let body = format!(
+                        r#"<?xml version="1.0" encoding="UTF-8"?><methodResponse>{fault}</methodResponse>"#
+                    );
+                    return StubResp::from((200, body));
+                }
+            };
+            StubResp::from((200, response(&value)))
+        })
+        .await;
+
+        let model = Model {
+            username: Some("rtorrent".to_owned()),
+            password: Some("rtorrent".to_owned()),
+            ..download_client_model(1, Category::Rtorrent, format!("{url}/RPC2"))
+        };
+        let client = || Downloader::from(model.clone());
+
+        client().connect_test().await.unwrap();
+
+        let magnet = "magnet:?xt=urn:btih:1ec0dbd01cfd4150b113bd95c4f02435e3a4d270";
+        let item = client().download_magnet(magnet).await.unwrap();
+        assert_eq!(item.id, ADDED.to_lowercase());
+        assert_eq!(item.status, ItemStatus::Downloading);
+        assert_eq!(item.path, Path::new("/data/added"));
+        assert_eq!(item.progress, 0.25);
+        assert_eq!(
+            (item.size, item.downloaded, item.uploaded),
+            (1000, 250, 500)
+        );
+        assert_eq!((item.download_speed, item.upload_speed), (75, 20));
+        assert_eq!(item.ratio, 0.5);
+        assert_eq!(item.eta, Some(Duration::from_secs(10)));
+        let added_at = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!((item.added_at, item.completed_at), (Some(added_at), None));
+        assert_eq!(item.seeding_time, None);
+        let file = DownloadFile {
+            path: "/data/added".into(),
+            size: 1000,
+            progress: 0.25,
+        };
+        assert_eq!(item.files, [file]);
+
+        let list = client().download_list().await.unwrap();
+        let seeding = list.iter().find(|it| it.id == "bbbb").unwrap();
+        assert!(seeding.seeding_time.is_some());
+        assert_eq!(seeding.message, None);
+        let error = list.iter().find(|it| it.id == "dddd").unwrap();
+        assert_eq!(error.message.as_deref(), Some("Storage error"));
+        assert!(error.files.is_empty() && error.files_error.is_some());
+        assert_eq!(seeding.files_error, None);
+        let mut list: Vec<_> = list.into_iter().map(|it| (it.id, it.status)).collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        let expect = [
+            (ADDED.to_lowercase(), ItemStatus::Downloading),
+            ("aaaa".to_owned(), ItemStatus::Downloading),
+            ("bbbb".to_owned(), ItemStatus::Downloaded),
+            ("cccc".to_owned(), ItemStatus::Complete),
+            ("dddd".to_owned(), ItemStatus::Error),
+        ];
+        assert_eq!(list, expect);
+
+        methods.lock().unwrap().clear();
+        client().pause("cccc").await.unwrap();
+        client().resume("cccc").await.unwrap();
+        client().recheck("cccc").await.unwrap();
+        client().set_category("cccc", "pvrr").await.unwrap();
+        client().remove("cccc", false).await.unwrap();
+        assert_eq!(
+            *methods.lock().unwrap(),
+            [
+                "d.stop",
+                "d.start",
+                "d.check_hash",
+                "d.custom1.set",
+                "d.multicall2",
+                "d.erase"
+            ]
+        );
+
+        let error = client().set_limits("cccc", &Default::default()).await;
+        assert!(error.is_err());
+        // 标签用于跟踪下载项，不能设置其他分类
+        let error = client().set_category("cccc", "tv").await;
+        assert!(error.is_err());
+    });
+}
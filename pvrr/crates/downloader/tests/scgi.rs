@@ -0,0 +1,75 @@
+use core::database::load_memory;
+use core::entity::download_client::{Category, Model};
+use downloader::{Downloader, ItemStatus};
+use std::sync::{Arc, Mutex};
+use test_util::download_client_model;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio_test::block_on;
+
+/// 启动 SCGI 测试服务，记录请求体，返回 scgi 地址
+async fn scgi_server(bodies: &Arc<Mutex<Vec<String>>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let bodies = bodies.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let mut stream = BufReader::new(stream);
+            let mut len = vec![];
+            stream.read_until(b':', &mut len).await.unwrap();
+            let len: usize = std::str::from_utf8(&len[..len.len() - 1])
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut headers = vec![0; len + 1];
+            stream.read_exact(&mut headers).await.unwrap();
+            let headers = String::from_utf8(headers).unwrap();
+            let mut headers = headers.split('\0');
+            assert_eq!(headers.next(), Some("CONTENT_LENGTH"));
+            let len: usize = headers.next().unwrap().parse().unwrap();
+            let mut body = vec![0; len];
+            stream.read_exact(&mut body).await.unwrap();
+            let body = String::from_utf8(body).unwrap();
+
+            let value = match body.contains("d.multicall2") {
+                true => "<array><data></data></array>",
+                false => "<i8>0</i8>",
+            };
+            bodies.lock().unwrap().push(body);
+            let resp = format!(
+                "Status: 200 OK\r\nContent-Type: text/xml\r\n\r\n\
+                <?xml version=\"1.0\"?><methodResponse><params><param>\
+                <value>{value}</value></param></params></methodResponse>"
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        }
+    });
+    format!("scgi://{addr}")
+}
+
+#[test]
+fn rtorrent_scgi_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let bodies = Arc::new(Mutex::new(vec![]));
+        let model = Model {
+            download_dir: r#"/downloads/"new" \movies"#.to_owned(),
+            ..download_client_model(1, Category::Rtorrent, scgi_server(&bodies).await)
+        };
+        let client = || Downloader::from(model.clone());
+
+        client().connect_test().await.unwrap();
+        let magnet = "magnet:?xt=urn:btih:1ec0dbd01cfd4150b113bd95c4f02435e3a4d270";
+        let item = client().download_magnet(magnet).await.unwrap();
+        assert_eq!(item.status, ItemStatus::Downloading);
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies[0].contains("<methodName>system.client_version</methodName>"));
+        // 下载路径中的引号和反斜杠需要转义
+        let dir = r#"d.directory.set=&quot;/downloads/\&quot;new\&quot; \\movies&quot;"#;
+        assert!(bodies[1].contains(dir), "{}", bodies[1]);
+        assert!(bodies[2].contains("<methodName>d.multicall2</methodName>"));
+    });
+}
@@ -0,0 +1,159 @@
+use core::database::{database, load_memory, ActiveModelTrait, EntityTrait, Set};
+use core::entity::{download_client, download_item};
+use core::event::{subscribe, SeedingTargetMet};
+use core::seeding::{SeedAction, SeedSource, SeedingRule};
+use core::setting::{DOWNLOAD_WATCH_INTERVAL, SEEDING_RULE};
+use downloader::{check_seeding_with, link_source, spawn_watcher, watch_once, Downloader};
+use serde_json::json;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+fn torrent(hash: &str, state: &str, ratio: f64, seeding_time: u64) -> serde_json::Value {
+    json!({
+        "hash": hash,
+        "state": state,
+        "content_path": format!("/downloads/{hash}"),
+        "ratio": ratio,
+        "seeding_time": seeding_time,
+    })
+}
+
+#[test]
+fn seeding_rule_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::<String>::new()));
+        let record = calls.clone();
+        let lists = Arc::new(AtomicU32::new(0));
+        let list_count = lists.clone();
+        let url = stub_server(move |req| {
+            let path = req.line.split(' ').nth(1).unwrap_or_default();
+            let body = match path {
+                "/api/v2/torrents/info?category=pvrr" => {
+                    list_count.fetch_add(1, Ordering::SeqCst);
+                    json!([
+                        torrent("a", "uploading", 2.0, 7200),
+                        torrent("b", "uploading", 0.5, 100),
+                        torrent("c", "stalledUP", 3.0, 10000),
+                        torrent("d", "downloading", 0.0, 0),
+                    ])
+                }
+                path if path.starts_with("/api/v2/torrents/files") => json!([]),
+                path => {
+                    record.lock().unwrap().push(format!("{path} {}", req.body));
+                    return StubResp::from((200, String::new()));
+                }
+            };
+            StubResp::from((200, body.to_string()))
+        })
+        .await;
+
+        let client = download_client::ActiveModel {
+            cat: Set(download_client::Category::Qbittorrent),
+            name: Set("qbittorrent".to_owned()),
+            url: Set(url),
+            download_dir: Set("/downloads".to_owned()),
+            local_dir: Set("/data".to_owned()),
+            ..Default::default()
+        };
+        let client = client.insert(database()).await.unwrap();
+        watch_once().await.unwrap();
+
+        // 关联下载项来源的索引器要求
+        let items = Downloader::from(client.clone()).download_list().await;
+        let items = items.unwrap();
+        let source = SeedSource {
+            indexer: 1,
+            minimum_ratio: Some(1.0),
+            minimum_seed_time: Some(3600),
+        };
+        for item in items.iter().filter(|it| it.id == "a" || it.id == "b") {
+            link_source(item, &source).await.unwrap();
+        }
+
+        // dry run 只生成报告
+        let mut met = subscribe::<SeedingTargetMet>();
+        let mut rule = SeedingRule {
+            enabled: true,
+            dry_run: true,
+            action: SeedAction::Pause,
+            ..Default::default()
+        };
+        let report = check_seeding_with(&rule).await.unwrap();
+        assert!(report.dry_run && report.errors.is_empty());
+        let mut decisions: Vec<_> = report.decisions.iter().collect();
+        decisions.sort_by(|a, b| a.id.cmp(&b.id));
+        let summary: Vec<_> = decisions
+            .iter()
+            .map(|it| (it.id.as_str(), it.indexer, it.action))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("a", Some(1), Some(SeedAction::Pause)),
+                ("b", Some(1), None)
+            ]
+        );
+        assert_eq!(decisions[0].seeding_time, Some(Duration::from_secs(7200)));
+        let event = met.try_recv().unwrap();
+        assert_eq!((event.id.as_str(), event.dry_run), ("a", true));
+        assert!(calls.lock().unwrap().is_empty());
+
+        // 已达到做种目标的下载项不再重复发布事件
+        check_seeding_with(&rule).await.unwrap();
+        assert!(met.try_recv().is_err());
+
+        // 执行操作后不再检查
+        rule.dry_run = false;
+        let report = check_seeding_with(&rule).await.unwrap();
+        assert!(report.decisions.iter().all(|it| it.error.is_none()));
+        assert!(met.try_recv().is_err());
+        assert_eq!(*calls.lock().unwrap(), ["/api/v2/torrents/pause hashes=a"]);
+        let report = check_seeding_with(&rule).await.unwrap();
+        let ids: Vec<_> = report.decisions.iter().map(|it| it.id.as_str()).collect();
+        assert_eq!(ids, ["b"]);
+
+        // 全局设置覆盖索引器要求，未设置的部分仍使用索引器要求
+        calls.lock().unwrap().clear();
+        rule.action = SeedAction::RemoveWithData;
+        rule.minimum_ratio = Some(0.4);
+        let report = check_seeding_with(&rule).await.unwrap();
+        let mut decisions: Vec<_> = report
+            .decisions
+            .iter()
+            .map(|it| (it.id.as_str(), it.action))
+            .collect();
+        decisions.sort_by_key(|it| it.0);
+        assert_eq!(
+            decisions,
+            [("b", None), ("c", Some(SeedAction::RemoveWithData))]
+        );
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["/api/v2/torrents/delete hashes=c&deleteFiles=true"]
+        );
+
+        let seeded = download_item::Entity::find().all(database()).await.unwrap();
+        let mut seeded: Vec<_> = seeded
+            .into_iter()
+            .filter(|it| it.seeded)
+            .map(|it| it.item_id)
+            .collect();
+        seeded.sort();
+        assert_eq!(seeded, ["a", "c"]);
+
+        // 后台任务的做种检查使用轮询获取的下载项列表，不重复获取
+        rule.dry_run = true;
+        SEEDING_RULE.set(&rule).await.unwrap();
+        DOWNLOAD_WATCH_INTERVAL.set(&60000).await.unwrap();
+        lists.store(0, Ordering::SeqCst);
+        let watcher = spawn_watcher();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        watcher.abort();
+        assert_eq!(lists.load(Ordering::SeqCst), 1);
+    });
+}
@@ -0,0 +1,142 @@
+use core::database::load_memory;
+use core::entity::download_client::{Category, Model};
+use downloader::{DownloadFile, Downloader, ItemStatus};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use test_util::{download_client_model, stub_server, StubResp};
+use tokio_test::block_on;
+
+fn torrent(hash: &str, status: u8, percent_done: f64, is_finished: bool, labels: &[&str]) -> Value {
+    json!({
+        "id": 1,
+        "name": format!("{hash}.mkv"),
+        "hashString": hash,
+        "percentDone": percent_done,
+        "rateUpload": 0,
+        "isFinished": is_finished,
+        "status": status,
+        "error": 0,
+        "labels": labels,
+    })
+}
+
+#[test]
+fn transmission_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        let session = Arc::new(AtomicU32::new(0));
+        let current = session.clone();
+        let url = stub_server(move |req| {
+            // 每次收到过期 session 时返回新的 session
+            let id = current.load(Ordering::SeqCst);
+            if req.header("X-Transmission-Session-Id") != Some(&format!("session-{id}")) {
+                let id = current.fetch_add(1, Ordering::SeqCst) + 1;
+                return StubResp {
+                    status: 409,
+                    headers: vec![("X-Transmission-Session-Id", format!("session-{id}"))],
+                    body: String::new(),
+                };
+            }
+            assert_eq!(req.header("authorization"), Some("Basic YWRtaW46cGFzcw=="));
+
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let arguments = match param["method"].as_str().unwrap() {
+                "port-test" => json!({ "port-is-open": true }),
+                "torrent-add" => {
+                    assert_eq!(param["arguments"]["labels"], json!(["pvrr"]));
+                    assert_eq!(param["arguments"]["download-dir"], "/downloads");
+                    let added = json!({ "id": 1, "name": "added.mkv", "hashString": "added" });
+                    json!({ "torrent-added": added })
+                }
+                "torrent-get" if param["arguments"]["ids"] == json!(["added"]) => {
+                    let mut added = torrent("added", 4, 0.25, false, &["pvrr"]);
+                    let detail = json!({
+                        "downloadDir": "/downloads", "sizeWhenDone": 1000, "downloadedEver": 250,
+                        "uploadedEver": 125, "rateDownload": 75, "rateUpload": 20, "uploadRatio": 0.5,
+                        "eta": 10, "secondsSeeding": 0, "addedDate": 1700000000, "doneDate": 0,
+                        "errorString": "", "files": [
+                            { "name": "added.mkv", "length": 1000, "bytesCompleted": 250 },
+                        ],
+                    });
+                    added.as_object_mut().unwrap().extend(detail.as_object().unwrap().clone());
+                    json!({ "torrents": [added] })
+                }
+                "torrent-get" => json!({ "torrents": [
+                    torrent("downloading", 4, 0.5, false, &["pvrr"]),
+                    torrent("checking", 2, 1.0, false, &["pvrr"]),
+                    json!({
+                        "id": 3, "name": "rechecking.mkv", "hashString": "rechecking", "percentDone": 0.5,
+                        "rateUpload": 0, "isFinished": false, "status": 2, "error": 0, "labels": ["pvrr"],
+                        "doneDate": 1700003600,
+                    }),
+                    torrent("seeding", 6, 1.0, false, &["pvrr"]),
+                    torrent("paused", 0, 1.0, false, &["pvrr"]),
+                    torrent("complete", 0, 1.0, true, &["pvrr"]),
+                    torrent("other", 6, 1.0, false, &["other"]),
+                    json!({
+                        "id": 2, "name": "error.mkv", "hashString": "error", "percentDone": 0.1,
+                        "rateUpload": 0, "isFinished": false, "status": 0, "error": 3, "labels": ["pvrr"],
+                    }),
+                ]}),
+                method => panic!("unexpected method {method}"),
+            };
+            let body = json!({ "result": "success", "arguments": arguments });
+            StubResp::from((200, body.to_string()))
+        })
+        .await;
+
+        let model = Model {
+            username: Some("admin".to_owned()),
+            password: Some("pass".to_owned()),
+            ..download_client_model(1, Category::Transmission, format!("{url}/transmission/rpc"))
+        };
+        let client = || Downloader::from(model.clone());
+
+        client().connect_test().await.unwrap();
+        assert_eq!(session.load(Ordering::SeqCst), 1);
+
+        let item = client().download(b"d4:infoe", "added").await.unwrap();
+        assert_eq!(item.id, "added");
+        assert_eq!(item.status, ItemStatus::Downloading);
+        assert_eq!(item.path, Path::new("/data/added.mkv"));
+        assert_eq!(item.progress, 0.25);
+        assert_eq!(
+            (item.size, item.downloaded, item.uploaded),
+            (1000, 250, 125)
+        );
+        assert_eq!((item.download_speed, item.upload_speed), (75, 20));
+        assert_eq!(item.ratio, 0.5);
+        assert_eq!(item.eta, Some(Duration::from_secs(10)));
+        assert_eq!(item.seeding_time, None);
+        let added_at = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!((item.added_at, item.completed_at), (Some(added_at), None));
+        assert_eq!(item.message, None);
+        let file = DownloadFile {
+            path: "/data/added.mkv".into(),
+            size: 1000,
+            progress: 0.25,
+        };
+        assert_eq!(item.files, [file]);
+
+        // session 过期后重新获取
+        session.fetch_add(1, Ordering::SeqCst);
+        let list = client().download_list().await.unwrap();
+        assert_eq!(session.load(Ordering::SeqCst), 3);
+        let list: Vec<_> = list.into_iter().map(|it| (it.id, it.status)).collect();
+        let expect = [
+            ("downloading", ItemStatus::Downloading),
+            ("checking", ItemStatus::Downloading),
+            ("rechecking", ItemStatus::Downloaded),
+            ("seeding", ItemStatus::Downloaded),
+            ("paused", ItemStatus::Downloaded),
+            ("complete", ItemStatus::Complete),
+            ("error", ItemStatus::Error),
+        ];
+        let expect: Vec<_> = expect.map(|(id, status)| (id.to_owned(), status)).into();
+        assert_eq!(list, expect);
+    });
+}
@@ -0,0 +1,84 @@
+use core::database::{database, load_memory, ActiveModelTrait, EntityTrait, Set};
+use core::entity::{download_client, download_item};
+use core::event::{subscribe, DownloadCompleted, DownloadStatusChanged};
+use downloader::{watch_once, Downloader, ItemStatus};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+#[test]
+fn download_watcher_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        // aria2 中的下载项，(status, completedLength)，aria2 返回的数字均为字符串
+        let state = Arc::new(Mutex::new(Some(("active", 10))));
+        let current = state.clone();
+        let url = stub_server(move |req| {
+            let param: Value = serde_json::from_str(&req.body).unwrap();
+            let active = match *current.lock().unwrap() {
+                Some((status, completed)) if param["method"] == "aria2.tellActive" => json!([{
+                    "gid": "1",
+                    "totalLength": "100",
+                    "completedLength": completed.to_string(),
+                    "status": status,
+                    "dir": "/downloads",
+                    "files": [{ "path": "/downloads/movie.mkv" }],
+                }]),
+                _ => json!([]),
+            };
+            StubResp::from((200, json!({ "result": active }).to_string()))
+        })
+        .await;
+
+        let client = download_client::ActiveModel {
+            cat: Set(download_client::Category::Aira2),
+            name: Set("aria2".to_owned()),
+            url: Set(format!("{url}/jsonrpc")),
+            download_dir: Set("/downloads".to_owned()),
+            local_dir: Set("/data".to_owned()),
+            ..Default::default()
+        };
+        let client = client.insert(database()).await.unwrap();
+        assert_eq!(Downloader::model(client.id).await.unwrap(), client);
+
+        let mut changed = subscribe::<DownloadStatusChanged>();
+        let mut completed = subscribe::<DownloadCompleted>();
+        let tracked = || async {
+            let list = download_item::Entity::find().all(database()).await.unwrap();
+            list.into_iter().map(|it| it.status).collect::<Vec<_>>()
+        };
+
+        // 首次发现下载项
+        watch_once().await.unwrap();
+        let event = changed.try_recv().unwrap();
+        assert_eq!((event.downloader, event.id.as_str()), (client.id, "1"));
+        assert_eq!((event.old, event.new), (None, ItemStatus::Downloading));
+        assert_eq!(tracked().await, [ItemStatus::Downloading]);
+
+        // 状态未变化时不发布事件
+        watch_once().await.unwrap();
+        assert!(changed.try_recv().is_err());
+
+        *state.lock().unwrap() = Some(("active", 100));
+        watch_once().await.unwrap();
+        let event = changed.try_recv().unwrap();
+        assert_eq!(event.old, Some(ItemStatus::Downloading));
+        assert_eq!(event.new, ItemStatus::Downloaded);
+        let event = completed.try_recv().unwrap();
+        assert_eq!(event.path, std::path::Path::new("/data/movie.mkv"));
+
+        // 做种完成不会重复发布下载完成事件
+        *state.lock().unwrap() = Some(("complete", 100));
+        watch_once().await.unwrap();
+        assert_eq!(changed.try_recv().unwrap().new, ItemStatus::Complete);
+        assert!(completed.try_recv().is_err());
+        assert_eq!(tracked().await, [ItemStatus::Complete]);
+
+        // 下载器中删除后不再跟踪
+        *state.lock().unwrap() = None;
+        watch_once().await.unwrap();
+        assert!(tracked().await.is_empty());
+    });
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// 电影分类 id
+pub const MOVIE_CATEGORY: u32 = 2000;
+/// 剧集分类 id
+pub const TV_CATEGORY: u32 = 5000;
+
+/// 缓存有效时间，过期后搜索时重新获取
+const CAPS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 索引器能力
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Caps {
+    pub server: CapsServer,
+    pub limits: CapsLimits,
+    /// 关键字搜索
+    pub search: Option<SearchMode>,
+    /// 剧集搜索
+    pub tv_search: Option<SearchMode>,
+    /// 电影搜索
+    pub movie_search: Option<SearchMode>,
+    pub categories: Vec<CapsCategory>,
+}
+
+/// 索引器服务信息
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CapsServer {
+    pub title: Option<String>,
+    pub version: Option<String>,
+    pub url: Option<String>,
+}
+
+/// 单次搜索返回的结果数量
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CapsLimits {
+    pub default: Option<u32>,
+    pub max: Option<u32>,
+}
+
+/// 搜索方式
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SearchMode {
+    pub available: bool,
+    /// 支持的搜索参数，例如 q、imdbid、season
+    pub supported_params: Vec<String>,
+}
+
+impl SearchMode {
+    pub fn supports(&self, param: &str) -> bool {
+        self.available && self.supported_params.iter().any(|it| it == param)
+    }
+}
+
+/// 搜索分类
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CapsCategory {
+    pub id: u32,
+    pub name: String,
+    pub subcats: Vec<CapsCategory>,
+}
+
+impl Caps {
+    /// 分类及其子分类中索引器支持的 id，不支持时为空
+    pub fn category_ids(&self, id: u32) -> Vec<u32> {
+        let Some(category) = self.categories.iter().find(|it| it.id == id) else {
+            return vec![];
+        };
+        let subcats = category.subcats.iter().map(|it| it.id);
+        [category.id].into_iter().chain(subcats).collect()
+    }
+}
+
+struct CachedCaps {
+    url: String,
+    apikey: String,
+    caps: Arc<Caps>,
+    fetched: Instant,
+}
+
+/// 各索引器的能力缓存，索引器 url、apikey 变化或缓存过期时重新获取
+static CAPS_CACHE: Lazy<RwLock<HashMap<u32, CachedCaps>>> = Lazy::new(RwLock::default);
+
+/// 获取缓存的索引器能力，不存在时使用 fetch 获取并缓存
+pub(crate) async fn cached<F>(id: u32, url: &str, apikey: &str, fetch: F) -> Result<Arc<Caps>>
+where
+    F: Future<Output = Result<Caps>>,
+{
+    if let Some(it) = CAPS_CACHE.read().unwrap().get(&id) {
+        if it.url == url && it.apikey == apikey && it.fetched.elapsed() < CAPS_TTL {
+            return Ok(it.caps.clone());
+        }
+    }
+    refresh(id, url, apikey, fetch).await
+}
+
+/// 重新获取索引器能力并更新缓存
+pub(crate) async fn refresh<F>(id: u32, url: &str, apikey: &str, fetch: F) -> Result<Arc<Caps>>
+where
+    F: Future<Output = Result<Caps>>,
+{
+    let caps = Arc::new(fetch.await?);
+    let cached = CachedCaps {
+        url: url.to_owned(),
+        apikey: apikey.to_owned(),
+        caps: caps.clone(),
+        fetched: Instant::now(),
+    };
+    CAPS_CACHE.write().unwrap().insert(id, cached);
+    Ok(caps)
+}
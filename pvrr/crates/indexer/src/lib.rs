@@ -1,14 +1,42 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
+mod caps;
+mod parse;
+mod quality;
+mod search;
 mod torznab;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Local};
+use core::database::{database, EntityTrait};
+use core::entity::index_client::{Category, Entity, Model};
+use core::event::{publish, IndexerSearchFinished};
+use core::seeding::SeedSource;
+use core::torrent::magnet_hash;
+use std::sync::Arc;
+
+pub use caps::{Caps, CapsCategory, CapsLimits, CapsServer, SearchMode};
+pub use caps::{MOVIE_CATEGORY, TV_CATEGORY};
+pub use core::quality::{ItemResolution, ItemSource};
+pub use parse::{parse_name, NameInfo};
+pub use quality::{Rejection, Scored, Scorer};
+pub use search::{Search, SearchReport, SEARCH_TIMEOUT};
+pub use torznab::TorznabError;
 
 /// 搜索 id 类型
+#[derive(Copy, Clone, Debug)]
 pub enum SearchId<'a> {
     TMDb(&'a str),
     Douban(&'a str),
     IMDb(&'a str),
+    TVDb(&'a str),
+}
+
+/// 搜索条件，索引器不支持 id 搜索时使用标题关键字
+pub(crate) struct SearchQuery<'a> {
+    pub(crate) id: SearchId<'a>,
+    pub(crate) key_word: &'a str,
+    /// 剧集搜索的季度、集数，搜索电影时为 None
+    pub(crate) episode: Option<(u16, u16)>,
 }
 
 pub enum Indexer {
@@ -16,61 +44,110 @@ pub enum Indexer {
 }
 
 impl Indexer {
-    /// 搜索电影
-    pub async fn search_movie(self, id: SearchId<'_>) -> Result<Vec<IndexItem>> {
-        Ok(self.search(id).await?.collect())
+    /// 获取数据库中保存的索引器配置
+    pub async fn model(id: u32) -> Result<Model> {
+        Entity::find_by_id(id)
+            .one(database())
+            .await?
+            .context("Can't find index client")
     }
 
-    /// 搜索剧集
-    pub async fn search_tv(self, id: SearchId<'_>, se: u16, ep: u16) -> Result<Vec<IndexItem>> {
-        let items = self.search(id).await?;
-        // 过滤符合条件的季度、集数
-        let items = items.filter(|it| it.season.contains(&se) && it.episode.contains(&ep));
-        Ok(items.collect())
+    /// 测试索引器连接，并刷新缓存的索引器能力
+    pub async fn connect_test(&self) -> Result<()> {
+        match self {
+            Indexer::Torznab(it) => it.connect_test().await,
+        }
     }
 
-    /// 执行搜索，将 id 转换为关键字，并将搜索结果附加解析后的季度等信息
-    async fn search(self, _id: SearchId<'_>) -> Result<impl Iterator<Item = IndexItem>> {
-        // todo covert_id_to_key_word
-        let key_word = "";
-        let items = match self {
-            Indexer::Torznab(it) => it.search(key_word).await,
-        }?;
-        let items = items.into_iter();
-        // todo append item info, such as SE, EP, source...
-        Ok(items)
+    /// 索引器支持的搜索方式、分类等信息，优先使用缓存
+    pub async fn caps(&self) -> Result<Arc<Caps>> {
+        match self {
+            Indexer::Torznab(it) => it.caps().await,
+        }
     }
-}
 
-/// 搜索结果来源
-pub enum ItemSource {
-    Unknown,
-    WebDL,
-    WebRip,
-}
+    /// 搜索电影，title 用于索引器不支持 id 搜索时
+    pub async fn search_movie(self, id: SearchId<'_>, title: &str) -> Result<Vec<IndexItem>> {
+        let query = SearchQuery {
+            id,
+            key_word: title,
+            episode: None,
+        };
+        Ok(self.search(query).await?.collect())
+    }
 
-impl Default for ItemSource {
-    fn default() -> Self {
-        Self::Unknown
+    /// 搜索剧集，title 用于索引器不支持 id 搜索时
+    pub async fn search_tv(
+        self,
+        id: SearchId<'_>,
+        title: &str,
+        se: u16,
+        ep: u16,
+    ) -> Result<Vec<IndexItem>> {
+        let query = SearchQuery {
+            id,
+            key_word: title,
+            episode: Some((se, ep)),
+        };
+        let items = self.search(query).await?;
+        // 过滤符合条件的季度、集数，整季资源包含该季的全部集数
+        let items = items.filter(|it| {
+            it.season.contains(&se) && (it.episode.is_empty() || it.episode.contains(&ep))
+        });
+        Ok(items.collect())
     }
-}
 
-/// 搜索结果分辨率
-pub enum ItemResolution {
-    Unknown,
+    /// 执行搜索，并将搜索结果附加解析后的季度等信息
+    async fn search<'a>(
+        self,
+        query: SearchQuery<'a>,
+    ) -> Result<impl Iterator<Item = IndexItem> + 'a> {
+        let (indexer, result) = match self {
+            Indexer::Torznab(it) => (it.id(), it.search(&query).await),
+        };
+        let result = result.map(|it| (it.items, it.skipped));
+        let skipped = result.as_ref().map(|it| it.1.clone()).unwrap_or_default();
+        publish(IndexerSearchFinished {
+            indexer,
+            key_word: query.key_word.to_owned(),
+            result: result
+                .as_ref()
+                .map(|it| it.0.len())
+                .map_err(|e| format!("{e:#}")),
+            skipped,
+        });
+        let (items, _) = result?;
+        let items = items.into_iter().map(move |mut it| {
+            it.indexer = indexer;
+            it.fill_name_info();
+            it
+        });
+        // 索引器返回了 id 时，不同 id 的结果即使标题相似也不使用
+        let id = query.id;
+        Ok(items.filter(move |it| it.matches_id(id) != Some(false)))
+    }
 }
 
-impl Default for ItemResolution {
-    fn default() -> Self {
-        Self::Unknown
+impl TryFrom<Model> for Indexer {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Model) -> Result<Self> {
+        match value.cat {
+            Category::Torznab => Ok(Self::Torznab(value.into())),
+            Category::Custom => bail!("Unsupported custom index client {}", value.name),
+        }
     }
 }
 
 /// 搜索结果条目
-#[derive(Default)]
+#[derive(Clone, Debug, Default)]
 pub struct IndexItem {
+    /// 来源索引器 id
+    indexer: u32,
     name: String,
     download_link: String,
+    info_hash: Option<String>,
+    magnet_url: Option<String>,
     byte_size: u64,
     pub_date: DateTime<Local>,
 
@@ -84,6 +161,15 @@ pub struct IndexItem {
     download_volume_factor: f64,
     upload_volume_factor: f64,
 
+    tvdb_id: Option<String>,
+    imdb_id: Option<String>,
+    tmdb_id: Option<String>,
+    /// 索引器分类 id
+    category: Vec<u32>,
+    /// 完成下载的次数
+    grabs: u64,
+    cover_url: Option<String>,
+
     season: Vec<u16>,
     episode: Vec<u16>,
     source: ItemSource,
@@ -97,11 +183,125 @@ impl IndexItem {
         Self {
             name,
             pub_date: Local::now(),
+            download_volume_factor: 1.0,
             upload_volume_factor: 1.0,
             ..Self::default()
         }
     }
 
+    /// 使用标题创建搜索结果并解析标题信息，用于不经过索引器直接评分
+    pub fn from_name(name: impl Into<String>) -> Self {
+        let mut item = Self::new(name.into());
+        item.fill_name_info();
+        item
+    }
+
+    pub fn with_byte_size(mut self, byte_size: u64) -> Self {
+        self.byte_size = byte_size;
+        self
+    }
+
+    pub fn with_seeders(mut self, seeders: u64) -> Self {
+        self.seeders = seeders;
+        self
+    }
+
+    pub fn with_download_volume_factor(mut self, factor: f64) -> Self {
+        self.download_volume_factor = factor;
+        self
+    }
+
+    /// 来源索引器及其做种要求，下载后用于关联下载项和做种规则
+    pub fn seed_source(&self) -> SeedSource {
+        SeedSource {
+            indexer: self.indexer,
+            minimum_ratio: Some(self.minimum_ratio).filter(|it| *it > 0.0),
+            minimum_seed_time: Some(self.minimum_seed_time)
+                .filter(|it| *it > 0)
+                .map(|it| u32::try_from(it).unwrap_or(u32::MAX)),
+        }
+    }
+
+    pub fn indexer(&self) -> u32 {
+        self.indexer
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn download_link(&self) -> &str {
+        &self.download_link
+    }
+
+    pub fn info_hash(&self) -> Option<&str> {
+        self.info_hash.as_deref()
+    }
+
+    pub fn byte_size(&self) -> u64 {
+        self.byte_size
+    }
+
+    pub fn seeders(&self) -> u64 {
+        self.seeders
+    }
+
+    pub fn magnet_url(&self) -> Option<&str> {
+        self.magnet_url.as_deref()
+    }
+
+    pub fn tvdb_id(&self) -> Option<&str> {
+        self.tvdb_id.as_deref()
+    }
+
+    pub fn imdb_id(&self) -> Option<&str> {
+        self.imdb_id.as_deref()
+    }
+
+    pub fn tmdb_id(&self) -> Option<&str> {
+        self.tmdb_id.as_deref()
+    }
+
+    /// 索引器分类 id
+    pub fn category(&self) -> &[u32] {
+        &self.category
+    }
+
+    /// 完成下载的次数
+    pub fn grabs(&self) -> u64 {
+        self.grabs
+    }
+
+    pub fn cover_url(&self) -> Option<&str> {
+        self.cover_url.as_deref()
+    }
+
+    /// 季度，为空时表示未识别
+    pub fn season(&self) -> &[u16] {
+        &self.season
+    }
+
+    /// 集数，季度不为空而集数为空时表示整季
+    pub fn episode(&self) -> &[u16] {
+        &self.episode
+    }
+
+    pub fn source(&self) -> ItemSource {
+        self.source
+    }
+
+    pub fn resolution(&self) -> ItemResolution {
+        self.resolution
+    }
+
+    pub fn streaming(&self) -> &str {
+        &self.streaming
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
     fn set_download_link(&mut self, link: String) {
         self.download_link = link;
     }
@@ -116,6 +316,13 @@ impl IndexItem {
         }
     }
 
+    fn set_info_hash(&mut self, info_hash: impl AsRef<str>) {
+        let info_hash = info_hash.as_ref().trim();
+        if !info_hash.is_empty() {
+            self.info_hash = Some(info_hash.to_ascii_lowercase());
+        }
+    }
+
     fn set_seeders(&mut self, seeders: impl AsRef<str>) {
         if let Ok(seeders) = seeders.as_ref().parse() {
             self.seeders = seeders;
@@ -158,15 +365,81 @@ impl IndexItem {
         }
     }
 
-    pub(crate) fn set_tvdb_id(&mut self, _tvdb_id: impl AsRef<str>) {
-        // if !tvdb_id.as_ref().is_empty() {
-        //     self.tvdb_id = Some(tvdb_id.as_ref().to_owned())
-        // }
+    fn set_magnet_url(&mut self, magnet_url: impl AsRef<str>) {
+        let magnet_url = magnet_url.as_ref().trim();
+        if !magnet_url.is_empty() {
+            self.magnet_url = Some(magnet_url.to_owned());
+        }
+    }
+
+    pub(crate) fn set_tvdb_id(&mut self, tvdb_id: impl AsRef<str>) {
+        self.tvdb_id = non_zero_id(tvdb_id.as_ref());
+    }
+
+    pub(crate) fn set_imdb_id(&mut self, imdb_id: impl AsRef<str>) {
+        self.imdb_id = imdb_id_of(imdb_id.as_ref());
+    }
+
+    fn set_tmdb_id(&mut self, tmdb_id: impl AsRef<str>) {
+        self.tmdb_id = non_zero_id(tmdb_id.as_ref());
+    }
+
+    fn add_category(&mut self, category: impl AsRef<str>) {
+        if let Ok(category) = category.as_ref().parse() {
+            self.category.push(category);
+        }
+    }
+
+    fn set_grabs(&mut self, grabs: impl AsRef<str>) {
+        if let Ok(grabs) = grabs.as_ref().parse() {
+            self.grabs = grabs;
+        }
     }
 
-    pub(crate) fn set_imdb_id(&mut self, _imdb_id: impl AsRef<str>) {
-        // if !imdb_id.as_ref().is_empty() {
-        //     self.imdb_id = Some(imdb_id.as_ref().to_owned())
-        // }
+    fn set_cover_url(&mut self, cover_url: impl AsRef<str>) {
+        let cover_url = cover_url.as_ref().trim();
+        if !cover_url.is_empty() {
+            self.cover_url = Some(cover_url.to_owned());
+        }
     }
+
+    /// 没有下载链接时使用 magnet 链接或 info hash，并从 magnet 链接中补充 info hash
+    fn fill_download_link(&mut self) {
+        if self.info_hash.is_none() {
+            let magnet = self.magnet_url.as_deref();
+            self.info_hash = magnet.and_then(|it| magnet_hash(it).ok());
+        }
+        if !self.download_link.is_empty() {
+            return;
+        }
+        if let Some(magnet) = &self.magnet_url {
+            self.download_link = magnet.clone();
+        } else if let Some(hash) = &self.info_hash {
+            self.download_link = format!("magnet:?xt=urn:btih:{hash}");
+        }
+    }
+
+    /// 搜索结果的 id 与搜索 id 是否相同，索引器未返回对应的 id 时为 None
+    pub fn matches_id(&self, id: SearchId<'_>) -> Option<bool> {
+        let (found, id) = match id {
+            SearchId::IMDb(id) => (self.imdb_id.as_ref(), imdb_id_of(id)),
+            SearchId::TMDb(id) => (self.tmdb_id.as_ref(), non_zero_id(id)),
+            SearchId::TVDb(id) => (self.tvdb_id.as_ref(), non_zero_id(id)),
+            SearchId::Douban(_) => (None, None),
+        };
+        Some(found? == id.as_ref()?)
+    }
+}
+
+/// 索引器使用 0 表示没有对应的 id
+fn non_zero_id(id: &str) -> Option<String> {
+    let id = id.trim();
+    let zero = id.trim_start_matches('0').is_empty();
+    Some(id.to_owned()).filter(|_| !zero)
+}
+
+/// imdb id 统一为 tt 开头的 7 位以上数字，e.g. tt0133093
+fn imdb_id_of(id: &str) -> Option<String> {
+    let id = id.trim().trim_start_matches("tt").parse::<u32>().ok();
+    id.filter(|it| *it > 0).map(|it| format!("tt{it:07}"))
 }
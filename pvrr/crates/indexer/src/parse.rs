@@ -0,0 +1,223 @@
+use crate::{IndexItem, ItemResolution, ItemSource};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+/// 单个标题中允许展开的最大季度、集数范围
+const RANGE_MAX: u16 = 200;
+
+/// 从资源标题中解析的信息
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameInfo {
+    /// 季度，为空时表示未识别
+    pub season: Vec<u16>,
+    /// 集数，季度不为空而集数为空时表示整季
+    pub episode: Vec<u16>,
+    pub source: ItemSource,
+    pub resolution: ItemResolution,
+    /// 流媒体，例如 Netflix
+    pub streaming: String,
+    /// 发布组
+    pub group: String,
+}
+
+/// 解析资源标题，未识别的部分保持默认值
+pub fn parse_name(name: &str) -> NameInfo {
+    let name = EXTENSION.replace(name.trim(), "");
+    let (season, episode) = parse_episode(&name);
+    NameInfo {
+        season,
+        episode,
+        source: parse_source(&name),
+        resolution: parse_resolution(&name),
+        streaming: parse_streaming(&name).unwrap_or_default().to_owned(),
+        group: parse_group(&name).unwrap_or_default(),
+    }
+}
+
+impl IndexItem {
+    /// 使用标题解析的信息填充季度、集数、来源等
+    pub(crate) fn fill_name_info(&mut self) {
+        let info = parse_name(&self.name);
+        self.season = info.season;
+        self.episode = info.episode;
+        self.source = info.source;
+        self.resolution = info.resolution;
+        self.streaming = info.streaming;
+        self.group = info.group;
+    }
+}
+
+fn regex(re: &str) -> Regex {
+    Regex::new(re).unwrap()
+}
+
+static EXTENSION: Lazy<Regex> = Lazy::new(|| regex(r"(?i)\.(mkv|mp4|avi|ts|m2ts|torrent)$"));
+/// 视频编码，避免 "5.1x264" 被识别为季度和集数
+static CODEC: Lazy<Regex> = Lazy::new(|| regex(r"(?i)(?-u:\b)[xh]\.?26[45](?-u:\b)"));
+
+// 标题中可能包含中文，使用 ascii 单词边界
+static SEASON_EPISODE: Lazy<[Regex; 2]> = Lazy::new(|| {
+    [
+        // e.g. "S01E03", "S01E01-E03", "S01E01E02"
+        regex(r"(?i)(?-u:\b)S(\d{1,2})[ ._-]?E(\d{1,4})(?:[-~&+]?E(\d{1,4})|-(\d{1,4}))?(?-u:\b)"),
+        // e.g. "2x01"
+        regex(r"(?i)(?-u:\b)(\d{1,2})x(\d{2,3})(?-u:\b)"),
+    ]
+});
+static SEASON: Lazy<[Regex; 3]> = Lazy::new(|| {
+    [
+        // e.g. "S01", "S01-S03"
+        regex(r"(?i)(?-u:\b)S(\d{1,2})(?:[-~&]S?(\d{1,2}))?(?-u:\b)"),
+        // e.g. "Season 1", "Seasons 1-3"
+        regex(r"(?i)(?-u:\b)Seasons?[ ._]?(\d{1,2})(?:[ ._]?[-~&][ ._]?(\d{1,2}))?(?-u:\b)"),
+        // e.g. "第1季", "第1-3季"
+        regex(r"第\s*(\d{1,2})(?:\s*[-~]\s*(\d{1,2}))?\s*季"),
+    ]
+});
+static EPISODE: Lazy<[Regex; 4]> = Lazy::new(|| {
+    [
+        // e.g. "E05", "EP05-08", "Episode 5"
+        regex(r"(?i)(?-u:\b)(?:EP?|Episode[ ._]?)(\d{1,4})(?:[-~]E?P?(\d{1,4}))?(?-u:\b)"),
+        // e.g. "第05集", "第01-10话"
+        regex(r"第\s*(\d{1,4})(?:\s*[-~]\s*(\d{1,4}))?\s*[集话話期]"),
+        // e.g. "[11]", "[11v2]"
+        regex(r"[\[【](\d{1,3})(?:v\d)?[\]】]"),
+        // e.g. "Title - 05 [1080p]", "Title - 05v2"
+        regex(r"\s-\s(\d{1,3})(?:v\d)?(?:\s|$|[\[【(])"),
+    ]
+});
+
+/// 解析季度和集数，只有集数时视为第一季
+fn parse_episode(name: &str) -> (Vec<u16>, Vec<u16>) {
+    let name = CODEC.replace_all(name, " ");
+    // 避免 "[720]" 被识别为集数
+    let name = RESOLUTIONS[2].replace_all(&name, " ");
+    let name = name.as_ref();
+    for re in SEASON_EPISODE.iter() {
+        if let Some(caps) = re.captures(name) {
+            let season = number(&caps, 1).into_iter().collect();
+            let end = number(&caps, 3).or_else(|| number(&caps, 4));
+            return (season, range(number(&caps, 2), end));
+        }
+    }
+    let find = |res: &[Regex]| {
+        let caps = res.iter().find_map(|it| it.captures(name));
+        caps.map(|it| range(number(&it, 1), number(&it, 2)))
+    };
+    let season = find(SEASON.as_slice()).unwrap_or_default();
+    let episode = find(EPISODE.as_slice()).unwrap_or_default();
+    match season.is_empty() && !episode.is_empty() {
+        true => (vec![1], episode),
+        false => (season, episode),
+    }
+}
+
+fn number(caps: &Captures, i: usize) -> Option<u16> {
+    caps.get(i).and_then(|it| it.as_str().parse().ok())
+}
+
+/// 展开范围，结束值无效时只使用开始值
+fn range(start: Option<u16>, end: Option<u16>) -> Vec<u16> {
+    let Some(start) = start else {
+        return vec![];
+    };
+    match end {
+        Some(end) if end > start && end - start <= RANGE_MAX => (start..=end).collect(),
+        _ => vec![start],
+    }
+}
+
+static SOURCES: Lazy<Vec<(Regex, ItemSource)>> = Lazy::new(|| {
+    let sources = [
+        (r"Remux", ItemSource::Remux),
+        (
+            r"(UHD-?)?Blu-?ray|BD(5|9|25|50)?|BD-?Rip|BR-?Rip",
+            ItemSource::BluRay,
+        ),
+        (r"WEB-?(DL-?)?Rip|WEB-?Cap", ItemSource::WebRip),
+        (r"WEB-?DL|WEB-?HD|WEB", ItemSource::WebDL),
+        (r"A?HD-?TV(-?Rip)?|UHD-?TV", ItemSource::HDTV),
+        (r"SD-?TV|TV-?Rip|PD-?TV|DSR|DVB", ItemSource::SDTV),
+        (r"HD-?DVD|DVD(-?Rip|5|9)?", ItemSource::DVD),
+        (
+            r"(HD-?)?CAM(-?Rip)?|(HD-?)?TS|TELESYNC|(HD-?)?TC|TELECINE",
+            ItemSource::Cam,
+        ),
+    ];
+    let sources = sources.into_iter();
+    let sources = sources.map(|(re, it)| (regex(&format!(r"(?i)(?-u:\b)({re})(?-u:\b)")), it));
+    sources.collect()
+});
+
+/// 解析来源，按列表顺序匹配，例如 WEB-DL 和 WEBRip 同时存在时为 WEBRip
+fn parse_source(name: &str) -> ItemSource {
+    let source = SOURCES.iter().find(|(re, _)| re.is_match(name));
+    source.map(|it| it.1).unwrap_or_default()
+}
+
+static RESOLUTIONS: Lazy<[Regex; 3]> = Lazy::new(|| {
+    [
+        // e.g. "1080p", "1920x1080", "[1080]"
+        regex(r"(?i)(?-u:\b)(480|576|720|1080|2160)[pi](?-u:\b)"),
+        regex(r"(?i)(?-u:\b)\d{3,4}x(480|576|720|1080|2160)(?-u:\b)"),
+        regex(r"[\[【(](480|576|720|1080|2160)[\]】)]"),
+    ]
+});
+static UHD: Lazy<Regex> = Lazy::new(|| regex(r"(?i)(?-u:\b)(4K|UHD)(?-u:\b)"));
+
+fn parse_resolution(name: &str) -> ItemResolution {
+    let resolution = RESOLUTIONS.iter().find_map(|it| it.captures(name));
+    match resolution.as_ref().map(|it| &it[1]) {
+        Some("480") => ItemResolution::P480,
+        Some("576") => ItemResolution::P576,
+        Some("720") => ItemResolution::P720,
+        Some("1080") => ItemResolution::P1080,
+        Some("2160") => ItemResolution::P2160,
+        _ if UHD.is_match(name) => ItemResolution::P2160,
+        _ => ItemResolution::Unknown,
+    }
+}
+
+/// 流媒体缩写，短缩写区分大小写以免误匹配
+static STREAMING: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+    let streaming = [
+        (r"NF|(?i:Netflix)", "Netflix"),
+        (r"(?i:AMZN|Amazon)", "Amazon"),
+        (r"(?i:DSNP|DSNY|Disney\+?)", "Disney+"),
+        (r"(?i:HMAX)", "HBO Max"),
+        (r"(?i:ATVP)", "Apple TV+"),
+        (r"(?i:HULU)", "Hulu"),
+        (r"(?i:PCOK)", "Peacock"),
+        (r"(?i:PMTP)", "Paramount+"),
+        (r"iT|(?i:iTunes)", "iTunes"),
+        (r"CR|(?i:Crunchyroll)", "Crunchyroll"),
+        (r"(?i:Baha)", "Bahamut"),
+    ];
+    let streaming = streaming.into_iter();
+    let streaming = streaming.map(|(re, it)| (regex(&format!(r"(?-u:\b)({re})(?-u:\b)")), it));
+    streaming.collect()
+});
+
+/// 解析流媒体，结尾的发布组不参与匹配，例如 "x265-CR" 中的 CR
+fn parse_streaming(name: &str) -> Option<&'static str> {
+    let name = GROUPS[0].replace(name, "");
+    let streaming = STREAMING.iter().find(|(re, _)| re.is_match(&name));
+    streaming.map(|it| it.1)
+}
+
+static GROUPS: Lazy<[Regex; 2]> = Lazy::new(|| {
+    [
+        // e.g. "Movie.2023.1080p.WEB-DL.x264-GROUP"
+        regex(r"-([A-Za-z0-9]+)$"),
+        // e.g. "[GROUP] Title - 01 [1080p]"
+        regex(r"^[\[【]([^\]】]+)[\]】]"),
+    ]
+});
+/// 不是发布组的结尾，例如 "WEB-DL"
+static NOT_GROUP: Lazy<Regex> = Lazy::new(|| regex(r"(?i)^(DL|Rip|HD|TV|Ray|\d+)$"));
+
+fn parse_group(name: &str) -> Option<String> {
+    let group = GROUPS.iter().filter_map(|it| it.captures(name));
+    let mut group = group.map(|it| it[1].trim().to_owned());
+    group.find(|it| !it.is_empty() && !NOT_GROUP.is_match(it))
+}
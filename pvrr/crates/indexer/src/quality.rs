@@ -0,0 +1,155 @@
+use crate::{IndexItem, ItemResolution, ItemSource};
+use core::quality::QualityProfile;
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter};
+
+/// 分辨率优先级每级的分数，高于来源和其他加分项
+const RESOLUTION_WEIGHT: i64 = 1000;
+/// 来源优先级每级的分数
+const SOURCE_WEIGHT: i64 = 100;
+/// 优先发布组的分数
+const GROUP_BONUS: i64 = 50;
+/// 每个优先关键字的分数
+const KEYWORD_BONUS: i64 = 10;
+
+/// 搜索结果被拒绝的原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    Resolution(ItemResolution),
+    Source(ItemSource),
+    Group(String),
+    Keyword(String),
+    /// 每分钟大小（MB）低于最小值
+    TooSmall {
+        per_minute: f64,
+        min: f64,
+    },
+    /// 每分钟大小（MB）超过最大值
+    TooLarge {
+        per_minute: f64,
+        max: f64,
+    },
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Resolution(it) => write!(f, "Resolution {it:?} is not allowed"),
+            Rejection::Source(it) => write!(f, "Source {it:?} is not allowed"),
+            Rejection::Group(it) => write!(f, "Release group `{it}` is rejected"),
+            Rejection::Keyword(it) => write!(f, "Title contains rejected keyword `{it}`"),
+            Rejection::TooSmall { per_minute, min } => {
+                write!(f, "Size {per_minute:.1} MB/min is below {min:.1} MB/min")
+            }
+            Rejection::TooLarge { per_minute, max } => {
+                write!(f, "Size {per_minute:.1} MB/min is above {max:.1} MB/min")
+            }
+        }
+    }
+}
+
+/// 评分后的搜索结果
+#[derive(Clone, Debug)]
+pub struct Scored {
+    pub item: IndexItem,
+    pub score: i64,
+    /// 拒绝原因，为空时表示可以下载
+    pub rejections: Vec<Rejection>,
+}
+
+impl Scored {
+    pub fn accepted(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+/// 根据质量配置对搜索结果评分
+pub struct Scorer<'a> {
+    profile: &'a QualityProfile,
+    /// 影片时长（分钟），用于计算每分钟大小
+    runtime: Option<u32>,
+}
+
+impl<'a> Scorer<'a> {
+    pub fn new(profile: &'a QualityProfile) -> Self {
+        Self {
+            profile,
+            runtime: None,
+        }
+    }
+
+    /// 设置影片时长（分钟），未设置时不检查大小
+    pub fn runtime(mut self, minutes: u32) -> Self {
+        self.runtime = Some(minutes).filter(|it| *it > 0);
+        self
+    }
+
+    /// 计算搜索结果的分数和拒绝原因
+    pub fn score(&self, item: IndexItem) -> Scored {
+        let profile = self.profile;
+        let mut score = 0;
+        let mut rejections = vec![];
+
+        match priority(&profile.resolutions, &item.resolution) {
+            Some(it) => score += it * RESOLUTION_WEIGHT,
+            None => rejections.push(Rejection::Resolution(item.resolution)),
+        }
+        match priority(&profile.sources, &item.source) {
+            Some(it) => score += it * SOURCE_WEIGHT,
+            None => rejections.push(Rejection::Source(item.source)),
+        }
+
+        let group = item.group.as_str();
+        let matches = |it: &&String| !group.is_empty() && it.eq_ignore_ascii_case(group);
+        if let Some(group) = profile.rejected_groups.iter().find(matches) {
+            rejections.push(Rejection::Group(group.clone()));
+        }
+        if profile.preferred_groups.iter().any(|it| matches(&it)) {
+            score += GROUP_BONUS;
+        }
+
+        let name = item.name.to_lowercase();
+        let contains = |it: &&String| !it.is_empty() && name.contains(&it.to_lowercase());
+        let rejected = profile.rejected_keywords.iter().filter(contains);
+        rejections.extend(rejected.map(|it| Rejection::Keyword(it.clone())));
+        let preferred = profile.preferred_keywords.iter().filter(contains).count();
+        score += preferred as i64 * KEYWORD_BONUS;
+
+        if let Some(runtime) = self.runtime {
+            let per_minute = item.byte_size as f64 / 1024.0 / 1024.0 / f64::from(runtime);
+            if let Some(min) = profile.min_size_per_minute.filter(|it| per_minute < *it) {
+                rejections.push(Rejection::TooSmall { per_minute, min });
+            }
+            if let Some(max) = profile.max_size_per_minute.filter(|it| per_minute > *it) {
+                rejections.push(Rejection::TooLarge { per_minute, max });
+            }
+        }
+
+        // 下载量系数为 0 时为完全免费
+        let free = (1.0 - item.download_volume_factor).clamp(0.0, 1.0);
+        score += (profile.freeleech_bonus as f64 * free).round() as i64;
+
+        Scored {
+            item,
+            score,
+            rejections,
+        }
+    }
+
+    /// 对搜索结果评分，返回按分数、做种数降序排列的可下载结果和被拒绝的结果
+    pub fn rank(&self, items: Vec<IndexItem>) -> (Vec<Scored>, Vec<Scored>) {
+        let scored = items.into_iter().map(|it| self.score(it));
+        let (mut accepted, rejected): (Vec<_>, Vec<_>) = scored.partition(Scored::accepted);
+        accepted.sort_by_key(|it| Reverse((it.score, it.item.seeders)));
+        (accepted, rejected)
+    }
+}
+
+/// 在优先级列表中的分数，排在越前分数越高，列表为空时不限制，不在列表中时返回 None
+fn priority<T: PartialEq>(allowed: &[T], value: &T) -> Option<i64> {
+    if allowed.is_empty() {
+        return Some(0);
+    }
+    let index = allowed.iter().position(|it| it == value)?;
+    Some((allowed.len() - index) as i64)
+}
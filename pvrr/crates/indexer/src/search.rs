@@ -0,0 +1,143 @@
+use crate::{IndexItem, Indexer, SearchId};
+use anyhow::{anyhow, Result};
+use core::database::{database, ColumnTrait, EntityTrait, QueryFilter};
+use core::entity::index_client::{Category, Column, Entity};
+use futures::future::join_all;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+/// 单个索引器的默认搜索超时时间
+pub const SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 聚合搜索结果
+#[derive(Clone, Debug, Default)]
+pub struct SearchReport {
+    /// 去重并排序后的搜索结果
+    pub items: Vec<IndexItem>,
+    /// 搜索失败或超时的索引器 id 和错误信息
+    pub errors: Vec<(u32, String)>,
+}
+
+/// 同时搜索全部索引器，单个索引器失败不影响其他索引器
+pub struct Search {
+    timeout: Duration,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            timeout: SEARCH_TIMEOUT,
+        }
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置单个索引器的搜索超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 搜索电影，title 用于索引器不支持 id 搜索时
+    pub async fn search_movie(&self, id: SearchId<'_>, title: &str) -> Result<SearchReport> {
+        self.search(|it| it.search_movie(id, title)).await
+    }
+
+    /// 搜索剧集，title 用于索引器不支持 id 搜索时
+    pub async fn search_tv(
+        &self,
+        id: SearchId<'_>,
+        title: &str,
+        se: u16,
+        ep: u16,
+    ) -> Result<SearchReport> {
+        self.search(|it| it.search_tv(id, title, se, ep)).await
+    }
+
+    async fn search<F, Fut>(&self, search: F) -> Result<SearchReport>
+    where
+        F: Fn(Indexer) -> Fut,
+        Fut: Future<Output = Result<Vec<IndexItem>>>,
+    {
+        // 自定义索引器只用于提供 cookies，不能搜索
+        let models = Entity::find()
+            .filter(Column::Cat.ne(Category::Custom))
+            .all(database())
+            .await?;
+        let searches = models.into_iter().map(|model| {
+            let id = model.id;
+            let search = Indexer::try_from(model).map(&search);
+            async move {
+                let items = match search {
+                    Ok(it) => tokio::time::timeout(self.timeout, it).await,
+                    Err(e) => return (id, Err(e)),
+                };
+                let items = items.unwrap_or_else(|_| Err(anyhow!("Search timed out")));
+                (id, items)
+            }
+        });
+
+        let mut report = SearchReport::default();
+        for (id, items) in join_all(searches).await {
+            match items {
+                Ok(it) => report.items.extend(it),
+                Err(e) => report.errors.push((id, format!("{e:#}"))),
+            }
+        }
+        report.items = rank(dedupe(report.items));
+        Ok(report)
+    }
+}
+
+/// 判断重复结果的依据
+#[derive(PartialEq, Eq, Hash)]
+enum DedupeKey {
+    InfoHash(String),
+    /// 规范化的标题和大小
+    Title(String, u64),
+}
+
+/// 去除不同索引器返回的相同资源，info hash 或规范化的标题和大小相同时视为重复，
+/// 重复时保留做种数较多的结果
+fn dedupe(items: Vec<IndexItem>) -> Vec<IndexItem> {
+    let mut deduped: Vec<IndexItem> = vec![];
+    let mut seen = HashMap::<DedupeKey, usize>::new();
+    for item in items {
+        let title = DedupeKey::Title(normalize(&item.name), item.byte_size);
+        let hash = item.info_hash.clone().map(DedupeKey::InfoHash);
+        let keys: Vec<_> = hash.into_iter().chain([title]).collect();
+        let index = match keys.iter().find_map(|it| seen.get(it)).copied() {
+            Some(index) if deduped[index].seeders < item.seeders => {
+                deduped[index] = item;
+                index
+            }
+            Some(index) => index,
+            None => {
+                deduped.push(item);
+                deduped.len() - 1
+            }
+        };
+        for key in keys {
+            seen.insert(key, index);
+        }
+    }
+    deduped
+}
+
+/// 只保留标题中的字母和数字并转为小写
+fn normalize(name: &str) -> String {
+    let chars = name.chars().filter(|it| it.is_alphanumeric());
+    chars.flat_map(char::to_lowercase).collect()
+}
+
+/// 按做种数、连接数、发布时间降序排列
+fn rank(mut items: Vec<IndexItem>) -> Vec<IndexItem> {
+    items.sort_by_key(|it| Reverse((it.seeders, it.peers, it.pub_date)));
+    items
+}
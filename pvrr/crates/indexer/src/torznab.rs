@@ -1,8 +1,75 @@
-use crate::IndexItem;
-use anyhow::Result;
-use core::request::direct;
+use crate::caps::{self, Caps, CapsCategory, CapsLimits, CapsServer, SearchMode};
+use crate::caps::{MOVIE_CATEGORY, TV_CATEGORY};
+use crate::{IndexItem, SearchId, SearchQuery};
+use anyhow::{ensure, Result};
+use core::entity::index_client::Model;
+use core::request::{direct, proxy, Req};
+use core::setting::INDEXER_MAX_PAGES;
 use serde::Deserialize;
 use std::borrow::Cow;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// 单次请求返回的最大结果数，索引器未提供时使用
+const DEFAULT_LIMIT: u32 = 100;
+
+/// 索引器返回的错误，e.g. `<error code="100" description="Incorrect user credentials"/>`
+#[derive(Debug, Error)]
+#[error("Torznab error {code}: {description}")]
+pub struct TorznabError {
+    pub code: u32,
+    pub description: String,
+}
+
+impl TorznabError {
+    /// apikey 错误、账号被禁用或权限不足
+    pub fn is_auth_error(&self) -> bool {
+        (100..200).contains(&self.code)
+    }
+
+    /// 达到请求或下载次数限制
+    pub fn is_limit_reached(&self) -> bool {
+        matches!(self.code, 500 | 501)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorTag {
+    #[serde(rename = "@code")]
+    code: u32,
+    #[serde(rename = "@description", default)]
+    description: String,
+}
+
+/// 索引器返回 error 时转换为 [TorznabError]
+fn check_error(text: &str) -> Result<()> {
+    match quick_xml::de::from_str::<ErrorTag>(text) {
+        Ok(it) => Err(TorznabError {
+            code: it.code,
+            description: it.description,
+        })?,
+        Err(_) => Ok(()),
+    }
+}
+
+/// 搜索结果，无效的条目会被跳过
+#[derive(Debug, Default)]
+pub(crate) struct SearchResult {
+    pub(crate) items: Vec<IndexItem>,
+    /// 跳过的条目标题或分页请求和原因
+    pub(crate) skipped: Vec<String>,
+}
+
+impl SearchResult {
+    /// 第一页失败时返回错误，之后的页失败时记录错误，保留已获取的结果
+    fn page_error(&mut self, offset: u32, e: anyhow::Error) -> Result<()> {
+        if offset == 0 {
+            return Err(e);
+        }
+        self.skipped.push(format!("Page offset {offset}: {e:#}"));
+        Ok(())
+    }
+}
 
 #[derive(Debug, Deserialize)]
 struct RssTag<'a> {
@@ -12,105 +79,367 @@ struct RssTag<'a> {
 
 #[derive(Debug, Deserialize)]
 struct ChannelTag<'a> {
-    #[serde(borrow)]
+    #[serde(default)]
+    response: Option<ResponseTag>,
+    #[serde(borrow, default)]
     item: Vec<ItemTag<'a>>,
 }
 
+/// 分页信息，e.g. `<newznab:response offset="0" total="1234"/>`
+#[derive(Debug, Deserialize)]
+struct ResponseTag {
+    #[serde(rename = "@total")]
+    total: Option<String>,
+}
+
+// 条目的字段都是可选的，无效的值在转换时处理，避免一个条目导致整个结果解析失败
 #[derive(Debug, Deserialize)]
 struct ItemTag<'a> {
     #[serde(rename = "title")]
-    title: TitleTag,
+    title: Option<TextTag<'a>>,
     #[serde(rename = "enclosure")]
-    enclosure: EnclosureTag,
+    enclosure: Option<EnclosureTag<'a>>,
     #[serde(rename = "size")]
-    size: SizeTag,
+    size: Option<TextTag<'a>>,
     #[serde(rename = "pubDate", borrow)]
-    pub_date: PubDateTag<'a>,
-    #[serde(rename = "attr", borrow)]
+    pub_date: Option<TextTag<'a>>,
+    #[serde(rename = "attr", borrow, default)]
     attrs: Vec<AttrTag<'a>>,
 }
 
 impl ItemTag<'_> {
-    fn into_item(self) -> IndexItem {
-        let mut site_info = IndexItem::new(self.title.text);
+    /// 转换为搜索结果，没有标题、下载链接或大小无效时返回原因
+    fn into_item(self) -> Result<IndexItem, String> {
+        let title = self.title.map(|it| it.text.trim().to_owned());
+        let Some(title) = title.filter(|it| !it.is_empty()) else {
+            return Err("<untitled>: Missing title".to_owned());
+        };
+        let mut site_info = IndexItem::new(title);
 
-        site_info.set_byte_size(self.size.value);
-        site_info.set_rfc2822_date(self.pub_date.text);
-        site_info.set_download_link(self.enclosure.url);
+        // 大小可能在 size、enclosure 的 length 或 size 属性中
+        let size = self.size.map(|it| it.text);
+        let size = size.or_else(|| self.enclosure.as_ref().and_then(|it| it.length.clone()));
+        let attr_size = self.attrs.iter().find(|it| it.name == "size");
+        let size = size.or_else(|| attr_size.map(|it| it.value.clone()));
+        if let Some(size) = size {
+            match size.trim().parse() {
+                Ok(size) => site_info.set_byte_size(size),
+                Err(_) => return Err(format!("{}: Invalid size `{size}`", site_info.name)),
+            }
+        }
+        if let Some(pub_date) = self.pub_date {
+            site_info.set_rfc2822_date(pub_date.text.trim());
+        }
+        if let Some(enclosure) = self.enclosure {
+            site_info.set_download_link(enclosure.url.trim().to_owned());
+        }
 
         self.attrs.iter().for_each(|it| match it.name.as_ref() {
             "seeders" => site_info.set_seeders(&it.value),
             "leechers" => site_info.set_leechers(&it.value),
             "peers" => site_info.set_peers(&it.value),
+            "infohash" => site_info.set_info_hash(&it.value),
             "minimumratio" => site_info.set_minimum_ratio(&it.value),
             "minimumseedtime" => site_info.set_minimum_seed_time(&it.value),
             "downloadvolumefactor" => site_info.set_download_volume_factor(&it.value),
             "uploadvolumefactor" => site_info.set_upload_volume_factor(&it.value),
             "tvdbid" => site_info.set_tvdb_id(&it.value),
             "imdbid" => site_info.set_imdb_id(&it.value),
+            "tmdbid" => site_info.set_tmdb_id(&it.value),
+            "magneturl" => site_info.set_magnet_url(&it.value),
+            "category" => site_info.add_category(&it.value),
+            "grabs" => site_info.set_grabs(&it.value),
+            "coverurl" => site_info.set_cover_url(&it.value),
             _ => {}
         });
+        site_info.fill_download_link();
 
-        site_info
+        match site_info.download_link.is_empty() {
+            true => Err(format!("{}: Missing download link", site_info.name)),
+            false => Ok(site_info),
+        }
     }
 }
 
 #[derive(Debug, Deserialize)]
-struct TitleTag {
-    #[serde(rename = "$value")]
-    text: String,
+struct TextTag<'a> {
+    #[serde(borrow, rename = "$value", default)]
+    text: Cow<'a, str>,
 }
 
 #[derive(Debug, Deserialize)]
-struct SizeTag {
-    #[serde(rename = "$value")]
-    value: u64,
+struct EnclosureTag<'a> {
+    #[serde(borrow, rename = "@url", default)]
+    url: Cow<'a, str>,
+    #[serde(borrow, rename = "@length")]
+    length: Option<Cow<'a, str>>,
 }
 
 #[derive(Debug, Deserialize)]
-struct PubDateTag<'a> {
-    #[serde(borrow, rename = "$value")]
-    text: Cow<'a, str>,
+struct AttrTag<'a> {
+    #[serde(borrow, rename = "@name", default)]
+    name: Cow<'a, str>,
+    #[serde(borrow, rename = "@value", default)]
+    value: Cow<'a, str>,
 }
 
 #[derive(Debug, Deserialize)]
-struct EnclosureTag {
+struct CapsTag {
+    #[serde(default)]
+    server: ServerTag,
+    #[serde(default)]
+    limits: LimitsTag,
+    #[serde(default)]
+    searching: SearchingTag,
+    #[serde(default)]
+    categories: CategoriesTag,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ServerTag {
+    #[serde(rename = "@title")]
+    title: Option<String>,
+    #[serde(rename = "@version")]
+    version: Option<String>,
     #[serde(rename = "@url")]
-    url: String,
+    url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LimitsTag {
+    #[serde(rename = "@default")]
+    default: Option<u32>,
+    #[serde(rename = "@max")]
+    max: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SearchingTag {
+    search: Option<SearchModeTag>,
+    #[serde(rename = "tv-search")]
+    tv_search: Option<SearchModeTag>,
+    #[serde(rename = "movie-search")]
+    movie_search: Option<SearchModeTag>,
 }
 
 #[derive(Debug, Deserialize)]
-struct AttrTag<'a> {
-    #[serde(borrow, rename = "@name")]
-    name: Cow<'a, str>,
-    #[serde(borrow, rename = "@value")]
-    value: Cow<'a, str>,
+struct SearchModeTag {
+    #[serde(rename = "@available")]
+    available: String,
+    #[serde(rename = "@supportedParams", default)]
+    supported_params: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CategoriesTag {
+    #[serde(default)]
+    category: Vec<CategoryTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CategoryTag {
+    #[serde(rename = "@id")]
+    id: u32,
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(default)]
+    subcat: Vec<CategoryTag>,
+}
+
+impl From<CapsTag> for Caps {
+    fn from(value: CapsTag) -> Self {
+        let server = CapsServer {
+            title: value.server.title,
+            version: value.server.version,
+            url: value.server.url,
+        };
+        let limits = CapsLimits {
+            default: value.limits.default,
+            max: value.limits.max,
+        };
+        let searching = value.searching;
+        let categories = value.categories.category.into_iter();
+        Self {
+            server,
+            limits,
+            search: searching.search.map(SearchMode::from),
+            tv_search: searching.tv_search.map(SearchMode::from),
+            movie_search: searching.movie_search.map(SearchMode::from),
+            categories: categories.map(CapsCategory::from).collect(),
+        }
+    }
+}
+
+impl From<SearchModeTag> for SearchMode {
+    fn from(value: SearchModeTag) -> Self {
+        let params = value.supported_params.split(',').map(str::trim);
+        let params = params.filter(|it| !it.is_empty()).map(str::to_owned);
+        Self {
+            available: value.available == "yes",
+            supported_params: params.collect(),
+        }
+    }
+}
+
+impl From<CategoryTag> for CapsCategory {
+    fn from(value: CategoryTag) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            subcats: value.subcat.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+/// 根据索引器支持的搜索方式生成搜索参数，不支持对应的 id 时使用关键字搜索
+/// 并限制搜索结果为索引器支持的电影或剧集分类
+fn search_params(caps: &Caps, query: &SearchQuery) -> Vec<(&'static str, String)> {
+    let (t, mode, category) = match query.episode {
+        Some(_) => ("tvsearch", caps.tv_search.as_ref(), TV_CATEGORY),
+        None => ("movie", caps.movie_search.as_ref(), MOVIE_CATEGORY),
+    };
+    let categories = caps.category_ids(category);
+    let categories = categories.iter().map(u32::to_string);
+    let categories = categories.collect::<Vec<_>>().join(",");
+    let category = Some(("cat", categories)).filter(|it| !it.1.is_empty());
+
+    let Some(mode) = mode.filter(|it| it.available) else {
+        let params = [("t", "search".to_owned()), ("q", query.key_word.to_owned())];
+        return params.into_iter().chain(category).collect();
+    };
+
+    let mut params = vec![("t", t.to_owned())];
+    params.extend(category);
+    let id = match query.id {
+        SearchId::IMDb(id) => Some(("imdbid", id)),
+        SearchId::TMDb(id) => Some(("tmdbid", id)),
+        SearchId::TVDb(id) => Some(("tvdbid", id)),
+        SearchId::Douban(_) => None,
+    };
+    match id.filter(|(name, id)| !id.is_empty() && mode.supports(name)) {
+        Some((name, id)) => params.push((name, id.to_owned())),
+        None => params.push(("q", query.key_word.to_owned())),
+    }
+    if let Some((season, episode)) = query.episode {
+        if mode.supports("season") {
+            params.push(("season", season.to_string()));
+        }
+        if mode.supports("ep") {
+            params.push(("ep", episode.to_string()));
+        }
+    }
+    params
 }
 
 pub struct Client {
+    id: u32,
     url: String,
     apikey: String,
+    use_proxy: bool,
 }
 
 impl Client {
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// 重新获取索引器能力，获取成功即连接正常
     pub(crate) async fn connect_test(&self) -> Result<()> {
-        let apikey = self.apikey.as_str();
-        let param = [("apikey", apikey), ("t", "caps")];
-        let req = direct().get(&self.url).query(&param);
-        let resp = req.send().await?;
-        resp.error_for_status()?;
+        caps::refresh(self.id, &self.url, &self.apikey, self.fetch_caps()).await?;
         Ok(())
     }
 
-    pub(crate) async fn search(&self, key_word: &str) -> Result<Vec<IndexItem>> {
+    pub(crate) async fn caps(&self) -> Result<Arc<Caps>> {
+        caps::cached(self.id, &self.url, &self.apikey, self.fetch_caps()).await
+    }
+
+    /// 按 offset 和 limit 分页搜索，直到没有更多结果或达到设置的最大页数，
+    /// 第一页之后的请求失败时停止分页，保留已获取的结果
+    pub(crate) async fn search(&self, query: &SearchQuery<'_>) -> Result<SearchResult> {
+        // 获取能力失败时按不支持任何搜索方式处理，使用关键字搜索
+        let caps = self.caps().await.unwrap_or_default();
+        let param = search_params(&caps, query);
+        let limit = caps.limits.max.or(caps.limits.default);
+        let limit = limit.filter(|it| *it > 0).unwrap_or(DEFAULT_LIMIT);
+        let max_pages = INDEXER_MAX_PAGES.get().await?.max(1);
+
+        let mut result = SearchResult::default();
+        for page in 0..max_pages {
+            let offset = page * limit;
+            let paging = [("offset", offset), ("limit", limit)];
+            let req = self.client()?.get(&self.url);
+            let req = req.query(&[("apikey", &self.apikey)]).query(&param);
+            let text = match self.send(req.query(&paging)).await {
+                Ok(it) => it,
+                Err(e) => {
+                    result.page_error(offset, e)?;
+                    break;
+                }
+            };
+            let rss: RssTag = match quick_xml::de::from_str(&text) {
+                Ok(it) => it,
+                Err(e) => {
+                    result.page_error(offset, e.into())?;
+                    break;
+                }
+            };
+
+            let count = rss.channel.item.len() as u32;
+            for item in rss.channel.item {
+                match item.into_item() {
+                    Ok(it) => result.items.push(it),
+                    Err(e) => result.skipped.push(e),
+                }
+            }
+            let total = rss.channel.response.and_then(|it| it.total);
+            let total = total.and_then(|it| it.trim().parse::<u32>().ok());
+            if count < limit || total.is_some_and(|it| offset + count >= it) {
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Client {
+    async fn fetch_caps(&self) -> Result<Caps> {
         let apikey = self.apikey.as_str();
-        let param = [("apikey", apikey), ("t", "search"), ("q", key_word)];
-        let req = direct().get(&self.url).query(&param);
+        let param = [("apikey", apikey), ("t", "caps")];
+        let req = self.client()?.get(&self.url).query(&param);
+        let text = self.send(req).await?;
+        let caps: CapsTag = quick_xml::de::from_str(&text)?;
+        Ok(caps.into())
+    }
+
+    /// 发送请求，索引器返回 error 时即使状态码不是 2xx 也返回 [TorznabError]
+    async fn send(&self, req: Req) -> Result<String> {
         let resp = req.send().await?;
-        let resp = resp.error_for_status()?;
+        let status = resp.status();
         let text = resp.text().await?;
-        let rss: RssTag = quick_xml::de::from_str(&text)?;
-        let items = rss.channel.item.into_iter().map(ItemTag::into_item);
-        Ok(items.collect())
+        check_error(&text)?;
+        ensure!(
+            status.is_success(),
+            "Torznab request failed with status {status}"
+        );
+        Ok(text)
+    }
+
+    fn client(&self) -> Result<core::request::Client> {
+        if self.use_proxy {
+            proxy()
+        } else {
+            Ok(direct())
+        }
+    }
+}
+
+impl From<Model> for Client {
+    fn from(value: Model) -> Self {
+        Self {
+            id: value.id,
+            url: value.url,
+            apikey: value.password.unwrap_or_default(),
+            use_proxy: value.use_proxy,
+        }
     }
 }
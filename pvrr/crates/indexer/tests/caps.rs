@@ -0,0 +1,119 @@
+use core::database::load_memory;
+use core::entity::index_client::{Category, Model};
+use core::request::RequestPolicy;
+use core::setting::REQUEST_POLICY;
+use indexer::{Indexer, SearchId};
+use std::sync::{Arc, Mutex};
+use test_util::{stub_server, StubResp};
+use tokio_test::block_on;
+
+const CAPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<caps>
+<server version="1.1" title="Jackett" url="http://localhost:9117/" />
+<limits default="100" max="500" />
+<searching>
+<search available="yes" supportedParams="q" />
+<tv-search available="yes" supportedParams="q, season, ep" />
+<movie-search available="yes" supportedParams="q,imdbid" />
+<music-search available="no" supportedParams="q" />
+</searching>
+<categories>
+<category id="2000" name="Movies">
+<subcat id="2030" name="Movies/SD" />
+<subcat id="2040" name="Movies/HD" />
+</category>
+<category id="5000" name="TV" />
+<category id="100401" name="Custom" />
+</categories>
+</caps>"#;
+
+const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+<channel>
+<item>
+<title>The Matrix 1999 1080p BluRay</title>
+<size>1024</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<enclosure url="http://localhost/movie.torrent" />
+<torznab:attr name="seeders" value="10" />
+</item>
+</channel>
+</rss>"#;
+
+#[test]
+fn torznab_caps_test() {
+    block_on(async {
+        // 测试服务不限速
+        load_memory().await.unwrap();
+        let policy = RequestPolicy {
+            interval: 0,
+            ..Default::default()
+        };
+        REQUEST_POLICY.set(&policy).await.unwrap();
+
+        let requests = Arc::new(Mutex::new(Vec::<String>::new()));
+        let record = requests.clone();
+        let url = stub_server(move |req| {
+            let path = req.line.split(' ').nth(1).unwrap_or_default();
+            let query = path.split_once('?').map(|it| it.1).unwrap_or_default();
+            record.lock().unwrap().push(query.to_owned());
+            let body = if query.ends_with("t=caps") { CAPS } else { RSS };
+            StubResp::from((200, body.to_owned()))
+        })
+        .await;
+        let with_apikey = |apikey: &str| {
+            let model = Model {
+                id: 2,
+                cat: Category::Torznab,
+                name: "jackett".to_owned(),
+                url: url.clone(),
+                use_proxy: false,
+                username: None,
+                password: Some(apikey.to_owned()),
+            };
+            Indexer::try_from(model).unwrap()
+        };
+        let indexer = || with_apikey("key");
+
+        let caps = indexer().caps().await.unwrap();
+        assert_eq!(caps.server.title.as_deref(), Some("Jackett"));
+        assert_eq!(
+            (caps.limits.default, caps.limits.max),
+            (Some(100), Some(500))
+        );
+        let tv = caps.tv_search.as_ref().unwrap();
+        assert_eq!(tv.supported_params, ["q", "season", "ep"]);
+        assert!(tv.supports("season") && !tv.supports("tvdbid"));
+        assert_eq!(caps.categories.len(), 3);
+        assert_eq!(caps.categories[0].subcats[1].name, "Movies/HD");
+        assert_eq!(caps.category_ids(2000), [2000, 2030, 2040]);
+        assert!(caps.category_ids(3000).is_empty());
+
+        // 搜索使用缓存的能力，只发送支持的分类
+        indexer()
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        indexer()
+            .search_tv(SearchId::TVDb("81189"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+        // 测试连接时刷新缓存
+        indexer().connect_test().await.unwrap();
+        indexer().caps().await.unwrap();
+        // 修改 apikey 后重新获取
+        with_apikey("new").caps().await.unwrap();
+        with_apikey("new").caps().await.unwrap();
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "apikey=key&t=caps",
+                "apikey=key&t=movie&cat=2000%2C2030%2C2040&imdbid=tt0133093&offset=0&limit=500",
+                "apikey=key&t=tvsearch&cat=5000&q=Breaking+Bad&season=1&ep=2&offset=0&limit=500",
+                "apikey=key&t=caps",
+                "apikey=new&t=caps",
+            ]
+        );
+    });
+}
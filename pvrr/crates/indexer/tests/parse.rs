@@ -0,0 +1,157 @@
+use indexer::{parse_name, ItemResolution, ItemSource, NameInfo};
+
+fn info(
+    season: &[u16],
+    episode: &[u16],
+    source: ItemSource,
+    resolution: ItemResolution,
+    streaming: &str,
+    group: &str,
+) -> NameInfo {
+    NameInfo {
+        season: season.to_vec(),
+        episode: episode.to_vec(),
+        source,
+        resolution,
+        streaming: streaming.to_owned(),
+        group: group.to_owned(),
+    }
+}
+
+#[test]
+fn parse_name_test() {
+    let cases = [
+        (
+            "The.Last.of.Us.S01E03.1080p.AMZN.WEB-DL.DDP5.1.H.264-NTb",
+            info(
+                &[1],
+                &[3],
+                ItemSource::WebDL,
+                ItemResolution::P1080,
+                "Amazon",
+                "NTb",
+            ),
+        ),
+        (
+            "Breaking Bad S05E01-E03 720p HDTV x264-GROUP",
+            info(
+                &[5],
+                &[1, 2, 3],
+                ItemSource::HDTV,
+                ItemResolution::P720,
+                "",
+                "GROUP",
+            ),
+        ),
+        (
+            "Stranger.Things.S04.2160p.NF.WEBRip.DDP5.1.x265-SiGMA.mkv",
+            info(
+                &[4],
+                &[],
+                ItemSource::WebRip,
+                ItemResolution::P2160,
+                "Netflix",
+                "SiGMA",
+            ),
+        ),
+        (
+            "The Office (US) Seasons 1-3 Complete DVDRip",
+            info(
+                &[1, 2, 3],
+                &[],
+                ItemSource::DVD,
+                ItemResolution::Unknown,
+                "",
+                "",
+            ),
+        ),
+        (
+            "Friends.3x12.480p.DD5.1x264",
+            info(
+                &[3],
+                &[12],
+                ItemSource::Unknown,
+                ItemResolution::P480,
+                "",
+                "",
+            ),
+        ),
+        (
+            "[LoliHouse] Spy x Family - 第05集 [WebRip 1080p HEVC-10bit AAC]",
+            info(
+                &[1],
+                &[5],
+                ItemSource::WebRip,
+                ItemResolution::P1080,
+                "",
+                "LoliHouse",
+            ),
+        ),
+        (
+            "[爪爪字幕组][欢迎来到实力至上主义的教室 第二季 S2][11][1080p]",
+            info(
+                &[2],
+                &[11],
+                ItemSource::Unknown,
+                ItemResolution::P1080,
+                "",
+                "爪爪字幕组",
+            ),
+        ),
+        (
+            "三体 第1季 第01-30集 4K WEB-DL",
+            info(
+                &[1],
+                &(1..=30).collect::<Vec<_>>(),
+                ItemSource::WebDL,
+                ItemResolution::P2160,
+                "",
+                "",
+            ),
+        ),
+        (
+            "The.Matrix.1999.1080p.UHD.BluRay.Remux.TrueHD.Atmos-FGT",
+            info(
+                &[],
+                &[],
+                ItemSource::Remux,
+                ItemResolution::P1080,
+                "",
+                "FGT",
+            ),
+        ),
+        (
+            "Oppenheimer 2023 HDCAM",
+            info(&[], &[], ItemSource::Cam, ItemResolution::Unknown, "", ""),
+        ),
+        (
+            "[Group] Some Movie [720]",
+            info(
+                &[],
+                &[],
+                ItemSource::Unknown,
+                ItemResolution::P720,
+                "",
+                "Group",
+            ),
+        ),
+        (
+            "[SubGroup] Anime Title - 05 [1080p]",
+            info(
+                &[1],
+                &[5],
+                ItemSource::Unknown,
+                ItemResolution::P1080,
+                "",
+                "SubGroup",
+            ),
+        ),
+        (
+            "Movie.2023.1080p.WEB-DL.H265-CR",
+            info(&[], &[], ItemSource::WebDL, ItemResolution::P1080, "", "CR"),
+        ),
+    ];
+    for (name, expect) in cases {
+        assert_eq!(parse_name(name), expect, "{name}");
+    }
+}
@@ -0,0 +1,120 @@
+use core::database::load_memory;
+use core::quality::QualityProfile;
+use core::setting::QUALITY_PROFILES;
+use indexer::{IndexItem, ItemResolution, Rejection, Scorer};
+use tokio_test::block_on;
+
+const MB: u64 = 1024 * 1024;
+
+/// 生成搜索结果，参数为标题、大小（MB）、做种数、下载量系数
+fn items(items: &[(&str, u64, u64, f64)]) -> Vec<IndexItem> {
+    let items = items.iter().map(|(title, size, seeders, factor)| {
+        IndexItem::from_name(*title)
+            .with_byte_size(size * MB)
+            .with_seeders(*seeders)
+            .with_download_volume_factor(*factor)
+    });
+    items.collect()
+}
+
+#[test]
+fn scorer_test() {
+    let items = items(&[
+        ("The Matrix 1999 Remux", 5000, 10, 1.0),
+        ("The Matrix 1999 CAM", 1000, 50, 1.0),
+        ("The Matrix 1999 Tiny", 100, 80, 1.0),
+        ("The Matrix 1999 Atmos", 1500, 5, 1.0),
+        ("The Matrix 1999", 1500, 20, 0.0),
+        ("The Matrix 1999 Proper", 1500, 30, 1.0),
+    ]);
+
+    // 时长 100 分钟，每分钟 5 MB 到 40 MB
+    let profile = QualityProfile {
+        name: "HD".to_owned(),
+        preferred_keywords: vec!["atmos".to_owned(), "PROPER".to_owned()],
+        rejected_keywords: vec!["cam".to_owned()],
+        min_size_per_minute: Some(5.0),
+        max_size_per_minute: Some(40.0),
+        freeleech_bonus: 15,
+        ..Default::default()
+    };
+    let scorer = Scorer::new(&profile).runtime(100);
+    let (accepted, rejected) = scorer.rank(items.clone());
+
+    // 免费加分高于关键字加分，分数相同时按做种数排序
+    let accepted: Vec<_> = accepted
+        .iter()
+        .map(|it| (it.item.name(), it.score))
+        .collect();
+    assert_eq!(
+        accepted,
+        [
+            ("The Matrix 1999", 15),
+            ("The Matrix 1999 Proper", 10),
+            ("The Matrix 1999 Atmos", 10),
+        ]
+    );
+    let rejected: Vec<_> = rejected
+        .iter()
+        .map(|it| (it.item.name(), it.rejections.clone()))
+        .collect();
+    assert_eq!(
+        rejected,
+        [
+            (
+                "The Matrix 1999 Remux",
+                vec![Rejection::TooLarge {
+                    per_minute: 50.0,
+                    max: 40.0
+                }]
+            ),
+            (
+                "The Matrix 1999 CAM",
+                vec![Rejection::Keyword("cam".to_owned())]
+            ),
+            (
+                "The Matrix 1999 Tiny",
+                vec![Rejection::TooSmall {
+                    per_minute: 1.0,
+                    min: 5.0
+                }]
+            ),
+        ]
+    );
+    assert_eq!(
+        rejected[0].1[0].to_string(),
+        "Size 50.0 MB/min is above 40.0 MB/min"
+    );
+
+    // 未设置时长时不检查大小，不在允许列表中的分辨率被拒绝
+    let profile = QualityProfile {
+        resolutions: vec![ItemResolution::P1080, ItemResolution::P720],
+        ..Default::default()
+    };
+    let scored = Scorer::new(&profile).score(items[0].clone());
+    assert_eq!(
+        scored.rejections,
+        [Rejection::Resolution(ItemResolution::Unknown)]
+    );
+}
+
+#[test]
+fn quality_profiles_test() {
+    block_on(async {
+        load_memory().await.unwrap();
+
+        // 名称不能为空或重复
+        let profile = |name: &str| QualityProfile {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let err = QUALITY_PROFILES
+            .set(&vec![profile("HD"), profile("HD")])
+            .await;
+        assert!(format!("{:#}", err.unwrap_err()).contains("Duplicate quality profile `HD`"));
+        assert!(QUALITY_PROFILES.set(&vec![profile(" ")]).await.is_err());
+        let profiles = vec![profile("HD"), profile("UHD")];
+        QUALITY_PROFILES.set(&profiles).await.unwrap();
+        assert_eq!(QUALITY_PROFILES.get().await.unwrap(), profiles);
+    });
+}
@@ -0,0 +1,106 @@
+use core::database::{database, load_memory, ActiveModelTrait, Set};
+use core::entity::index_client::{ActiveModel, Category};
+use core::request::RequestPolicy;
+use core::setting::REQUEST_POLICY;
+use indexer::{Indexer, Search, SearchId};
+use std::time::Duration;
+use test_util::{caps, rss, stub_server, torznab_server, RssItem, StubResp};
+use tokio::net::TcpListener;
+use tokio_test::block_on;
+
+/// 模拟 Torznab 索引器，参数为标题、大小、做种数、info hash
+async fn torznab(items: &'static [(&'static str, u64, u64, &'static str)]) -> String {
+    let caps = caps(r#"<movie-search available="yes" supportedParams="q,imdbid" />"#);
+    torznab_server(caps, move |_| {
+        let items = items.iter().map(|(title, size, seeders, hash)| {
+            let item = RssItem::new(title).size(*size).seeders(*seeders);
+            item.attr("infohash", hash)
+        });
+        StubResp::from((200, rss(items)))
+    })
+    .await
+}
+
+async fn add_indexer(name: &str, url: String) -> u32 {
+    let model = ActiveModel {
+        cat: Set(Category::Torznab),
+        name: Set(name.to_owned()),
+        url: Set(url),
+        use_proxy: Set(false),
+        username: Set(None),
+        password: Set(Some("key".to_owned())),
+        ..Default::default()
+    };
+    model.insert(database()).await.unwrap().id
+}
+
+#[test]
+fn aggregate_search_test() {
+    block_on(async {
+        // 测试服务不限速、不重试
+        load_memory().await.unwrap();
+        let policy = RequestPolicy {
+            interval: 0,
+            max_retries: 0,
+            ..Default::default()
+        };
+        REQUEST_POLICY.set(&policy).await.unwrap();
+
+        let first = torznab(&[
+            ("The.Matrix.1999.1080p.BluRay", 100, 5, "AAAA"),
+            ("The Matrix 1999 720p WEB-DL", 50, 20, ""),
+            ("The Matrix 1999 2160p UHD", 300, 1, ""),
+        ])
+        .await;
+        let second = torznab(&[
+            // 与第一个索引器的结果 info hash 相同
+            ("The Matrix (1999) 1080p BluRay x264", 101, 8, "aaaa"),
+            // 规范化后标题和大小相同
+            ("the matrix 1999 720p web dl", 50, 3, ""),
+            ("The Matrix 1999 480p DVDRip", 30, 2, "bbbb"),
+        ])
+        .await;
+        let failed = stub_server(|_| StubResp::from((401, String::new()))).await;
+        // 不响应请求的索引器
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hanging = format!("http://{}", listener.local_addr().unwrap());
+
+        add_indexer("first", first).await;
+        add_indexer("second", second).await;
+        let failed = add_indexer("failed", failed).await;
+        assert_eq!(Indexer::model(failed).await.unwrap().name, "failed");
+        let hanging = add_indexer("hanging", hanging).await;
+
+        let report = Search::new()
+            .timeout(Duration::from_millis(500))
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+
+        // 去重后按做种数排序
+        let items: Vec<_> = report
+            .items
+            .iter()
+            .map(|it| (it.name(), it.seeders()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("The Matrix 1999 720p WEB-DL", 20),
+                ("The Matrix (1999) 1080p BluRay x264", 8),
+                ("The Matrix 1999 480p DVDRip", 2),
+                ("The Matrix 1999 2160p UHD", 1),
+            ]
+        );
+        assert_eq!(report.items[1].info_hash(), Some("aaaa"));
+
+        // 单个索引器失败不影响其他索引器
+        let mut errors = report.errors;
+        errors.sort();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, failed);
+        assert!(errors[0].1.contains("401"));
+        assert_eq!(errors[1], (hanging, "Search timed out".to_owned()));
+        drop(listener);
+    });
+}
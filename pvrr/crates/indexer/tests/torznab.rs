@@ -0,0 +1,375 @@
+use core::database::load_memory;
+use core::entity::index_client::{Category, Model};
+use core::event::{subscribe, IndexerSearchFinished};
+use core::request::RequestPolicy;
+use core::setting::{INDEXER_MAX_PAGES, REQUEST_POLICY};
+use indexer::{Indexer, SearchId, TorznabError};
+use std::sync::{Arc, Mutex};
+use test_util::{caps, rss, stub_server, torznab_server, RssItem, StubResp};
+use tokio_test::block_on;
+
+fn titles_rss(titles: &[&str]) -> String {
+    rss(titles.iter().map(|it| RssItem::new(it)))
+}
+
+fn search_caps(movie: &str, tv: &str) -> String {
+    caps(&format!(
+        r#"<search available="yes" supportedParams="q" />
+<tv-search {tv} />
+<movie-search {movie} />"#
+    ))
+}
+
+fn indexer(url: String) -> Indexer {
+    let model = Model {
+        id: 1,
+        cat: Category::Torznab,
+        name: "torznab".to_owned(),
+        url,
+        use_proxy: false,
+        username: None,
+        password: Some("key".to_owned()),
+    };
+    Indexer::try_from(model).unwrap()
+}
+
+/// 记录搜索请求的查询参数，返回服务地址
+async fn torznab(caps: String, queries: &Arc<Mutex<Vec<String>>>) -> String {
+    let queries = queries.clone();
+    torznab_server(caps, move |query| {
+        queries.lock().unwrap().push(query.to_owned());
+        StubResp::from((200, titles_rss(&["Movie 2023 1080p WEB-DL"])))
+    })
+    .await
+}
+
+#[test]
+fn torznab_search_test() {
+    block_on(async {
+        // 测试服务不限速
+        load_memory().await.unwrap();
+        let policy = RequestPolicy {
+            interval: 0,
+            ..Default::default()
+        };
+        REQUEST_POLICY.set(&policy).await.unwrap();
+
+        let queries = Arc::new(Mutex::new(vec![]));
+        let url = torznab(
+            search_caps(
+                r#"available="yes" supportedParams="q,imdbid""#,
+                r#"available="yes" supportedParams="q,tvdbid,season,ep""#,
+            ),
+            &queries,
+        )
+        .await;
+
+        let items = indexer(url.clone())
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        // 不支持的 id 使用标题搜索
+        indexer(url.clone())
+            .search_movie(SearchId::TMDb("603"), "The Matrix")
+            .await
+            .unwrap();
+        indexer(url.clone())
+            .search_tv(SearchId::TVDb("81189"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+        indexer(url)
+            .search_tv(SearchId::Douban("1"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *queries.lock().unwrap(),
+            [
+                "apikey=key&t=movie&imdbid=tt0133093&offset=0&limit=100",
+                "apikey=key&t=movie&q=The+Matrix&offset=0&limit=100",
+                "apikey=key&t=tvsearch&tvdbid=81189&season=1&ep=2&offset=0&limit=100",
+                "apikey=key&t=tvsearch&q=Breaking+Bad&season=1&ep=2&offset=0&limit=100",
+            ]
+        );
+
+        let queries = Arc::new(Mutex::new(vec![]));
+        let unavailable = search_caps(r#"available="no""#, r#"available="no""#);
+        let url = torznab(unavailable, &queries).await;
+
+        indexer(url.clone())
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        indexer(url)
+            .search_tv(SearchId::TVDb("81189"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+
+        // 索引器不支持电影、剧集搜索时使用关键字搜索
+        assert_eq!(
+            *queries.lock().unwrap(),
+            [
+                "apikey=key&t=search&q=The+Matrix&offset=0&limit=100",
+                "apikey=key&t=search&q=Breaking+Bad&offset=0&limit=100",
+            ]
+        );
+
+        // 按标题解析的季度、集数过滤，整季资源匹配该季的任意一集
+        let titles = [
+            "Breaking.Bad.S01E02.720p.HDTV.x264-GROUP",
+            "Breaking.Bad.S01E03.720p.HDTV.x264-GROUP",
+            "Breaking.Bad.S01.1080p.BluRay.x264-GROUP",
+            "Breaking.Bad.S01E01-E05.1080p.WEB-DL-GROUP",
+            "Breaking.Bad.S02E02.720p.HDTV.x264-GROUP",
+            "Breaking.Bad.S02-S05.1080p.BluRay-GROUP",
+        ];
+        let url = torznab_server("<caps></caps>", move |_| {
+            StubResp::from((200, titles_rss(&titles)))
+        });
+        let url = url.await;
+        let items = indexer(url)
+            .search_tv(SearchId::TVDb("81189"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+        let items: Vec<_> = items.iter().map(|it| it.name()).collect();
+        assert_eq!(items, [titles[0], titles[2], titles[3]]);
+
+        // 解析 torznab 属性，没有 enclosure 时使用 magnet 链接或 info hash
+        let url = torznab_server("<caps></caps>", move |_| {
+            StubResp::from((200, ATTRS_RSS.to_owned()))
+        });
+        let url = url.await;
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("133093"), "The Matrix")
+            .await
+            .unwrap();
+        let names: Vec<_> = items.iter().map(|it| it.name()).collect();
+        // imdb id 不同的结果被过滤
+        assert_eq!(
+            names,
+            ["The Matrix 1999", "The Matrix Magnet", "The Matrix Hash"]
+        );
+
+        let item = &items[0];
+        assert_eq!(item.download_link(), "http://localhost/matrix.torrent");
+        assert_eq!(item.imdb_id(), Some("tt0133093"));
+        assert_eq!(item.tmdb_id(), Some("603"));
+        assert_eq!(item.tvdb_id(), None);
+        assert_eq!(item.category(), [2000, 2040]);
+        assert_eq!(item.grabs(), 42);
+        assert_eq!(item.cover_url(), Some("http://localhost/cover.jpg"));
+        assert_eq!(item.matches_id(SearchId::TMDb("603")), Some(true));
+        assert_eq!(item.matches_id(SearchId::TVDb("81189")), None);
+
+        let magnet = "magnet:?xt=urn:btih:0123456789ABCDEF0123456789ABCDEF01234567&dn=matrix";
+        assert_eq!(items[1].download_link(), magnet);
+        assert_eq!(items[1].magnet_url(), Some(magnet));
+        let hash = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(items[1].info_hash(), Some(hash));
+        assert_eq!(
+            items[2].download_link(),
+            "magnet:?xt=urn:btih:89abcdef0123456789abcdef0123456789abcdef"
+        );
+
+        // 无效的条目被跳过，不影响其他结果
+        let url = torznab_server("<caps></caps>", move |_| {
+            StubResp::from((200, MALFORMED_RSS.to_owned()))
+        });
+        let url = url.await;
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        let names: Vec<_> = items.iter().map(|it| it.name()).collect();
+        assert_eq!(names, ["The Matrix 1999", "The Matrix Length"]);
+        assert_eq!(items[0].byte_size(), 0);
+        assert_eq!(items[1].byte_size(), 2048);
+
+        // 没有结果时 channel 中没有 item
+        let empty = r#"<rss version="2.0"><channel><title>empty</title></channel></rss>"#;
+        let url = torznab_server("<caps></caps>", move |_| {
+            StubResp::from((200, empty.to_owned()))
+        });
+        let url = url.await;
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert!(items.is_empty());
+
+        // 获取能力失败时使用关键字搜索
+        let queries = Arc::new(Mutex::new(vec![]));
+        let record = queries.clone();
+        let url = stub_server(move |req| {
+            let path = req.line.split(' ').nth(1).unwrap_or_default();
+            let query = path.split_once('?').map(|it| it.1).unwrap_or_default();
+            if query.ends_with("t=caps") {
+                return StubResp::from((500, "Internal Server Error".to_owned()));
+            }
+            record.lock().unwrap().push(query.to_owned());
+            StubResp::from((200, titles_rss(&["The Matrix 1999"])))
+        })
+        .await;
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        let record = queries.clone();
+        let url = torznab_server("not xml", move |query| {
+            record.lock().unwrap().push(query.to_owned());
+            StubResp::from((200, titles_rss(&["Breaking Bad S01E02"])))
+        })
+        .await;
+        let items = indexer(url)
+            .search_tv(SearchId::TVDb("81189"), "Breaking Bad", 1, 2)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            [
+                "apikey=key&t=search&q=The+Matrix&offset=0&limit=100",
+                "apikey=key&t=search&q=Breaking+Bad&offset=0&limit=100",
+            ]
+        );
+
+        // 索引器返回的错误
+        let url = stub_server(move |_| {
+            let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<error code="100" description="Invalid API Key" />"#;
+            StubResp::from((401, body.to_owned()))
+        })
+        .await;
+        let err = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap_err();
+        let err = err.downcast::<TorznabError>().unwrap();
+        assert_eq!(err.code, 100);
+        assert_eq!(err.description, "Invalid API Key");
+        assert!(err.is_auth_error());
+        assert!(!err.is_limit_reached());
+
+        // 按 caps 中的 limit 分页，直到结果不足一页
+        let queries = Arc::new(Mutex::new(vec![]));
+        let record = queries.clone();
+        let limits = r#"<caps><limits default="2" max="2" /></caps>"#;
+        let url = torznab_server(limits, move |query| {
+            record.lock().unwrap().push(query.to_owned());
+            let body = match query.contains("offset=0&") {
+                true => titles_rss(&["The Matrix 1", "The Matrix 2"]),
+                false => titles_rss(&["The Matrix 3"]),
+            };
+            StubResp::from((200, body))
+        })
+        .await;
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            [
+                "apikey=key&t=search&q=The+Matrix&offset=0&limit=2",
+                "apikey=key&t=search&q=The+Matrix&offset=2&limit=2",
+            ]
+        );
+
+        // 之后的页请求失败时保留已获取的结果，并报告失败的分页
+        let limits = r#"<caps><limits default="2" max="2" /></caps>"#;
+        let url = torznab_server(limits, move |query| match query.contains("offset=0&") {
+            true => StubResp::from((200, titles_rss(&["The Matrix 1", "The Matrix 2"]))),
+            false => StubResp::from((400, String::new())),
+        })
+        .await;
+        let mut finished = subscribe::<IndexerSearchFinished>();
+        let items = indexer(url.clone())
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        let event = finished.try_recv().unwrap();
+        assert_eq!(event.result, Ok(2));
+        assert_eq!(event.skipped.len(), 1);
+        assert!(event.skipped[0].starts_with("Page offset 2:"));
+
+        // 最大页数可以设置
+        INDEXER_MAX_PAGES.set(&1).await.unwrap();
+        let items = indexer(url)
+            .search_movie(SearchId::IMDb("tt0133093"), "The Matrix")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        let event = finished.try_recv().unwrap();
+        assert!(event.skipped.is_empty());
+    });
+}
+
+const ATTRS_RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+<channel>
+<item>
+<title>The Matrix 1999</title>
+<size>1024</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<enclosure url="http://localhost/matrix.torrent" />
+<torznab:attr name="imdbid" value="0133093" />
+<torznab:attr name="tmdbid" value="603" />
+<torznab:attr name="tvdbid" value="0" />
+<torznab:attr name="category" value="2000" />
+<torznab:attr name="category" value="2040" />
+<torznab:attr name="grabs" value="42" />
+<torznab:attr name="coverurl" value="http://localhost/cover.jpg" />
+</item>
+<item>
+<title>The Matrix Reloaded 2003</title>
+<size>1024</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<enclosure url="http://localhost/reloaded.torrent" />
+<torznab:attr name="imdbid" value="tt0234215" />
+</item>
+<item>
+<title>The Matrix Magnet</title>
+<size>1024</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<torznab:attr name="magneturl" value="magnet:?xt=urn:btih:0123456789ABCDEF0123456789ABCDEF01234567&amp;dn=matrix" />
+</item>
+<item>
+<title>The Matrix Hash</title>
+<size>1024</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<torznab:attr name="infohash" value="89ABCDEF0123456789ABCDEF0123456789ABCDEF" />
+</item>
+</channel>
+</rss>"#;
+
+const MALFORMED_RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+<channel>
+<item>
+<title>The Matrix 1999</title>
+<enclosure url="http://localhost/matrix.torrent" />
+</item>
+<item>
+<size>1024</size>
+<enclosure url="http://localhost/untitled.torrent" />
+</item>
+<item>
+<title>The Matrix Invalid Size</title>
+<size>1.2 GB</size>
+<enclosure url="http://localhost/invalid.torrent" />
+</item>
+<item>
+<title>The Matrix No Link</title>
+<size>1024</size>
+</item>
+<item>
+<title>The Matrix Length</title>
+<pubDate>invalid date</pubDate>
+<enclosure url="http://localhost/length.torrent" length="2048" />
+</item>
+</channel>
+</rss>"#;
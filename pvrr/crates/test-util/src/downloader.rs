@@ -0,0 +1,17 @@
+use core::entity::download_client::{Category, Model};
+
+/// 测试使用的下载器配置，下载目录 /downloads 映射到本地目录 /data
+pub fn download_client_model(id: u32, cat: Category, url: String) -> Model {
+    Model {
+        id,
+        cat,
+        name: format!("{cat:?}"),
+        url,
+        username: None,
+        password: None,
+        download_dir: "/downloads".to_owned(),
+        local_dir: "/data".to_owned(),
+        category: None,
+        path_mappings: Default::default(),
+    }
+}
@@ -0,0 +1,111 @@
+//! 各 crate 集成测试共用的本地 http 测试服务
+
+#[cfg(feature = "core")]
+mod downloader;
+mod torznab;
+
+#[cfg(feature = "core")]
+pub use downloader::download_client_model;
+pub use torznab::{caps, rss, torznab_server, RssItem};
+
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 测试服务收到的请求
+pub struct StubReq {
+    /// 请求行，例如 "POST /v1 HTTP/1.1"
+    pub line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubReq {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let header = self
+            .headers
+            .iter()
+            .find(|it| it.0.eq_ignore_ascii_case(name));
+        header.map(|it| it.1.as_str())
+    }
+}
+
+/// 测试服务返回的响应
+pub struct StubResp {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl From<(u16, String)> for StubResp {
+    fn from((status, body): (u16, String)) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body,
+        }
+    }
+}
+
+/// 启动本地测试 http 服务，返回服务地址
+pub async fn stub_server<F, R>(handler: F) -> String
+where
+    F: Fn(StubReq) -> R + Send + Sync + 'static,
+    R: Into<StubResp>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move { handle(stream, handler.as_ref()).await });
+        }
+    });
+    format!("http://{addr}")
+}
+
+async fn handle<F, R>(stream: TcpStream, handler: &F)
+where
+    F: Fn(StubReq) -> R,
+    R: Into<StubResp>,
+{
+    let mut stream = BufReader::new(stream);
+
+    let mut line = String::new();
+    stream.read_line(&mut line).await.unwrap();
+    let mut headers = vec![];
+    loop {
+        let mut header = String::new();
+        stream.read_line(&mut header).await.unwrap();
+        match header.trim().split_once(':') {
+            Some((key, value)) => headers.push((key.trim().into(), value.trim().into())),
+            None => break,
+        }
+    }
+
+    let req = StubReq {
+        line: line.trim().into(),
+        headers,
+        body: String::new(),
+    };
+    let len = req.header("content-length").and_then(|it| it.parse().ok());
+    let mut body = vec![0; len.unwrap_or(0)];
+    stream.read_exact(&mut body).await.unwrap();
+    let req = StubReq {
+        body: String::from_utf8(body).unwrap(),
+        ..req
+    };
+
+    let resp = handler(req).into();
+    let mut head = format!("HTTP/1.1 {} STUB\r\n", resp.status);
+    for (key, value) in resp.headers {
+        head.push_str(&format!("{key}: {value}\r\n"));
+    }
+    let len = resp.body.len();
+    head.push_str(&format!(
+        "Content-Length: {len}\r\nConnection: close\r\n\r\n"
+    ));
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(resp.body.as_bytes()).await.unwrap();
+}
@@ -0,0 +1,104 @@
+use crate::{stub_server, StubResp};
+
+/// Torznab 搜索结果条目
+pub struct RssItem {
+    title: String,
+    size: u64,
+    seeders: u64,
+    attrs: Vec<(&'static str, String)>,
+}
+
+impl RssItem {
+    /// 大小为 1024、做种数为 10 的条目
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            size: 1024,
+            seeders: 10,
+            attrs: vec![],
+        }
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn seeders(mut self, seeders: u64) -> Self {
+        self.seeders = seeders;
+        self
+    }
+
+    /// 添加 torznab 属性，例如 infohash、downloadvolumefactor
+    pub fn attr(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.attrs.push((name, value.to_string()));
+        self
+    }
+}
+
+/// 生成 Torznab 搜索结果
+pub fn rss(items: impl IntoIterator<Item = RssItem>) -> String {
+    let items: String = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let RssItem {
+                title,
+                size,
+                seeders,
+                attrs,
+            } = item;
+            let attrs: String = attrs
+                .iter()
+                .map(|(name, value)| {
+                    format!("\n<torznab:attr name=\"{name}\" value=\"{value}\" />")
+                })
+                .collect();
+            format!(
+                r#"<item>
+<title>{title}</title>
+<size>{size}</size>
+<pubDate>Mon, 01 May 2023 12:00:00 +0000</pubDate>
+<enclosure url="http://localhost/{index}.torrent" length="{size}" type="application/x-bittorrent" />
+<torznab:attr name="seeders" value="{seeders}" />{attrs}
+</item>"#
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+<channel>{items}</channel>
+</rss>"#
+    )
+}
+
+/// 生成索引器能力，参数为 searching 中的搜索类型
+pub fn caps(searching: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<caps>
+<searching>
+{searching}
+</searching>
+</caps>"#
+    )
+}
+
+/// 启动 Torznab 测试服务，caps 请求返回给定的能力，其他请求的查询参数交给 handler 处理
+pub async fn torznab_server<F, R>(caps: impl Into<String>, handler: F) -> String
+where
+    F: Fn(&str) -> R + Send + Sync + 'static,
+    R: Into<StubResp>,
+{
+    let caps = caps.into();
+    stub_server(move |req| {
+        let path = req.line.split(' ').nth(1).unwrap_or_default();
+        let query = path.split_once('?').map(|it| it.1).unwrap_or_default();
+        if query.ends_with("t=caps") {
+            return StubResp::from((200, caps.clone()));
+        }
+        handler(query).into()
+    })
+    .await
+}